@@ -0,0 +1,131 @@
+use http::header::ETAG;
+use http::{HeaderValue, Response};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use yew::format::Binary;
+
+// Successful responses to GET requests, so that data can be shown straight away while it is
+// fetched again in the background
+#[derive(Default)]
+pub struct ResponseCache {
+    entries: RefCell<HashMap<String, CachedResponse>>,
+    // The latest background request for each entry, so that an older or invalidated one doesn't
+    // overwrite newer data
+    revalidating: RefCell<HashMap<String, u64>>,
+    next_id: Cell<u64>,
+    // Increased for each entry whenever it is invalidated, so that a response to a request which
+    // was already in flight isn't stored over the change
+    generations: RefCell<HashMap<String, u64>>,
+}
+
+#[derive(Clone)]
+pub struct CachedResponse {
+    pub etag: Option<HeaderValue>,
+    pub body: Vec<u8>,
+    // Just fetched in the background and not shown yet, so doesn't need revalidating when used
+    pub fresh: bool,
+}
+
+impl ResponseCache {
+    pub fn key(path: &str, query: &[(String, String)]) -> String {
+        if query.is_empty() {
+            return path.to_string();
+        }
+        let query = serde_urlencoded::to_string(query).unwrap();
+        format!("{}?{}", path, query)
+    }
+
+    pub fn get(&self, key: &str) -> Option<CachedResponse> {
+        self.entries.borrow().get(key).cloned()
+    }
+
+    // To be taken when the request is made, and given back with its response to store
+    pub fn generation(&self, key: &str) -> u64 {
+        *self
+            .generations
+            .borrow_mut()
+            .entry(key.to_string())
+            .or_insert(0)
+    }
+
+    // Whether the entry has been invalidated since the generation was taken
+    pub fn is_stale(&self, key: &str, generation: u64) -> bool {
+        self.generations.borrow().get(key) != Some(&generation)
+    }
+
+    pub fn store(&self, key: String, generation: u64, response: &Response<Binary>, fresh: bool) {
+        if self.is_stale(&key, generation) {
+            return;
+        }
+        if let (true, Ok(body)) = (response.status().is_success(), response.body()) {
+            let etag = response.headers().get(ETAG).cloned();
+            let body = body.clone();
+            self.entries
+                .borrow_mut()
+                .insert(key, CachedResponse { etag, body, fresh });
+        }
+    }
+
+    // Whether the entry was marked as fresh, which is cleared as it is about to be shown
+    pub fn take_fresh(&self, key: &str) -> bool {
+        match self.entries.borrow_mut().get_mut(key) {
+            Some(e) => std::mem::replace(&mut e.fresh, false),
+            None => false,
+        }
+    }
+
+    // For data which was included in another response
+    pub fn prime(&self, key: String, body: Vec<u8>) {
+        let entry = CachedResponse {
+            etag: None,
+            body,
+            fresh: false,
+        };
+        self.entries.borrow_mut().insert(key, entry);
+    }
+
+    // Removes everything under the same top level path, since a change to one item also affects
+    // the lists which contain it
+    pub fn invalidate(&self, path: &str) {
+        let resource = path.split(['/', '?']).next().unwrap_or("");
+        let matches = |key: &String| key.split(['/', '?']).next().unwrap_or("") == resource;
+        self.entries.borrow_mut().retain(|k, _| !matches(k));
+        self.revalidating.borrow_mut().retain(|k, _| !matches(k));
+        for (_, g) in self
+            .generations
+            .borrow_mut()
+            .iter_mut()
+            .filter(|(k, _)| matches(k))
+        {
+            *g += 1;
+        }
+    }
+
+    pub fn clear(&self) {
+        self.entries.borrow_mut().clear();
+        self.revalidating.borrow_mut().clear();
+        for g in self.generations.borrow_mut().values_mut() {
+            *g += 1;
+        }
+    }
+
+    // Starts a background request for the key, which replaces any earlier one
+    pub fn start_revalidation(&self, key: String) -> u64 {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        self.revalidating.borrow_mut().insert(key, id);
+        id
+    }
+
+    // Whether the response to the request may be stored, as nothing has replaced or invalidated it
+    pub fn finish_revalidation(&self, key: &str, id: u64) -> bool {
+        let mut revalidating = self.revalidating.borrow_mut();
+        match revalidating.get(key) {
+            Some(x) if *x == id => {
+                revalidating.remove(key);
+                true
+            }
+            _ => false,
+        }
+    }
+}
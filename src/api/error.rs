@@ -1,7 +1,9 @@
-use http::StatusCode;
+use http::{HeaderMap, StatusCode};
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 use std::str;
+use std::time::{Duration, SystemTime};
 use yew::format::Binary;
 use yew::services::fetch::Response;
 
@@ -9,6 +11,9 @@ use yew::services::fetch::Response;
 pub struct Details {
     pub code: String,
     pub description: Option<String>,
+    // Validation errors for individual form fields, keyed by field name
+    #[serde(default)]
+    pub fields: Option<HashMap<String, String>>,
 }
 
 #[allow(dead_code)]
@@ -18,22 +23,46 @@ pub enum APIError {
     Unauthorized(Details),
     Forbidden,
     NotFound,
+    Conflict(Option<Details>),
+    PayloadTooLarge,
+    TooManyRequests(Option<Duration>), // How long to wait before trying again, if known
     InternalServerError,
     UnknownError(StatusCode),
     DeserializeError,
     RequestError,
+    Timeout,
+    Cancelled,
+    Queued, // Saved to be sent once the API can be reached again
 }
 
 impl std::error::Error for APIError {}
 
+impl APIError {
+    // The validation error for a form field, if the API rejected it
+    pub fn field_error(&self, field: &str) -> Option<&str> {
+        match self {
+            APIError::BadRequest(d) => d.fields.as_ref()?.get(field).map(|x| x.as_str()),
+            _ => None,
+        }
+    }
+
+    // How long the user is locked out for, after making too many requests
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            APIError::TooManyRequests(d) => *d,
+            _ => None,
+        }
+    }
+}
+
 impl Display for APIError {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
         let m = match self {
-            APIError::BadRequest(d) => d
-                .description
-                .as_ref()
-                .unwrap_or(&"".to_string())
-                .to_string(),
+            APIError::BadRequest(d) => match (&d.description, &d.fields) {
+                (Some(x), _) => x.to_string(),
+                (None, Some(_)) => "Please correct the errors above".to_string(),
+                (None, None) => "".to_string(),
+            },
             APIError::Unauthorized(d) => match d.code.as_str() {
                 "INCORRECT_CREDENTIALS" => "Incorrect credentials",
                 "MISSING_CREDENTIALS" => "Request missing username or password",
@@ -42,10 +71,29 @@ impl Display for APIError {
             .to_string(),
             APIError::Forbidden => "Forbidden".to_string(),
             APIError::NotFound => "Resource not found".to_string(),
+            APIError::Conflict(d) => match d.as_ref().and_then(|d| d.description.as_ref()) {
+                Some(x) => x.to_string(),
+                None => "This was changed by someone else, please reload and try again".to_string(),
+            },
+            APIError::PayloadTooLarge => {
+                "The upload is too large for the server to accept".to_string()
+            }
+            APIError::TooManyRequests(d) => match d {
+                Some(d) => format!(
+                    "Too many attempts, please try again in {} seconds",
+                    d.as_secs()
+                ),
+                None => "Too many attempts, please try again later".to_string(),
+            },
             APIError::InternalServerError => "Internal server error".to_string(),
             APIError::UnknownError(c) => format!("Unknown Error (HTTP {})", c.as_u16()),
             APIError::DeserializeError => "Could not parse server response".to_string(),
             APIError::RequestError => "Request failed, please check your connection".to_string(),
+            APIError::Timeout => "Request timed out, please check your connection".to_string(),
+            APIError::Cancelled => "Request cancelled".to_string(),
+            APIError::Queued => {
+                "You're offline, the change will be sent once the connection is back".to_string()
+            }
         };
         write!(f, "{}", m)
     }
@@ -58,9 +106,9 @@ where
     if let (meta, Ok(bin)) = response.into_parts() {
         let data = str::from_utf8(&bin).map_err(|_| APIError::DeserializeError)?;
         if meta.status.is_success() {
-            serde_json::from_str(&data).map_err(|_| APIError::DeserializeError)
+            serde_json::from_str(data).map_err(|_| APIError::DeserializeError)
         } else {
-            let details: Option<Details> = serde_json::from_str(&data).ok();
+            let details: Option<Details> = serde_json::from_str(data).ok();
             Err(match meta.status {
                 StatusCode::BAD_REQUEST if details.is_some() => {
                     APIError::BadRequest(details.unwrap())
@@ -70,6 +118,11 @@ where
                 }
                 StatusCode::FORBIDDEN => APIError::Forbidden,
                 StatusCode::NOT_FOUND => APIError::NotFound,
+                StatusCode::CONFLICT => APIError::Conflict(details),
+                StatusCode::PAYLOAD_TOO_LARGE => APIError::PayloadTooLarge,
+                StatusCode::TOO_MANY_REQUESTS => {
+                    APIError::TooManyRequests(retry_after(&meta.headers, now()))
+                }
                 StatusCode::INTERNAL_SERVER_ERROR => APIError::InternalServerError,
                 _ => APIError::UnknownError(meta.status),
             })
@@ -78,3 +131,158 @@ where
         Err(APIError::RequestError)
     }
 }
+
+// Retry-After is either a number of seconds, or a HTTP date
+fn retry_after(headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    let value = headers
+        .get(http::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    // A date which has already passed means there is no need to wait
+    Some(date.duration_since(now).unwrap_or_default())
+}
+
+// SystemTime::now panics in the browser, so the time is taken from JavaScript there instead
+#[cfg(target_arch = "wasm32")]
+fn now() -> SystemTime {
+    std::time::UNIX_EPOCH + Duration::from_millis(js_sys::Date::now() as u64)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn now() -> SystemTime {
+    SystemTime::now()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::header::RETRY_AFTER;
+    use serde::de::IgnoredAny;
+    use std::time::UNIX_EPOCH;
+    use yew::format::Nothing;
+
+    fn response(status: StatusCode, body: &str) -> Response<Binary> {
+        Response::builder()
+            .status(status)
+            .body(Ok(body.as_bytes().to_vec()))
+            .unwrap()
+    }
+
+    fn error(status: StatusCode, body: &str) -> APIError {
+        resolve::<IgnoredAny>(response(status, body)).unwrap_err()
+    }
+
+    const DETAILS: &str = r#"{"code": "INVALID", "fields": {"email": "Already in use"}}"#;
+
+    #[test]
+    fn success_is_deserialized() {
+        let body = r#"{"code": "OK", "description": "Done"}"#;
+        let details: Details = resolve(response(StatusCode::OK, body)).unwrap();
+        assert_eq!(details.description.as_deref(), Some("Done"));
+        let invalid = resolve::<Details>(response(StatusCode::OK, "<html>"));
+        assert_eq!(invalid.unwrap_err(), APIError::DeserializeError);
+    }
+
+    #[test]
+    fn errors_with_details() {
+        let bad_request = error(StatusCode::BAD_REQUEST, DETAILS);
+        assert_eq!(bad_request.field_error("email"), Some("Already in use"));
+        assert_eq!(bad_request.to_string(), "Please correct the errors above");
+        let unauthorized = error(
+            StatusCode::UNAUTHORIZED,
+            r#"{"code": "INCORRECT_CREDENTIALS"}"#,
+        );
+        assert_eq!(unauthorized.to_string(), "Incorrect credentials");
+        let conflict = error(
+            StatusCode::CONFLICT,
+            r#"{"code": "CONFLICT", "description": "Stale"}"#,
+        );
+        assert_eq!(conflict.to_string(), "Stale");
+    }
+
+    #[test]
+    fn errors_without_details() {
+        let cases = vec![
+            (
+                StatusCode::BAD_REQUEST,
+                APIError::UnknownError(StatusCode::BAD_REQUEST),
+            ),
+            (
+                StatusCode::UNAUTHORIZED,
+                APIError::UnknownError(StatusCode::UNAUTHORIZED),
+            ),
+            (StatusCode::FORBIDDEN, APIError::Forbidden),
+            (StatusCode::NOT_FOUND, APIError::NotFound),
+            (StatusCode::CONFLICT, APIError::Conflict(None)),
+            (StatusCode::PAYLOAD_TOO_LARGE, APIError::PayloadTooLarge),
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                APIError::TooManyRequests(None),
+            ),
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                APIError::InternalServerError,
+            ),
+            (
+                StatusCode::BAD_GATEWAY,
+                APIError::UnknownError(StatusCode::BAD_GATEWAY),
+            ),
+        ];
+        for (status, expected) in cases {
+            assert_eq!(error(status, "Not JSON"), expected, "HTTP {}", status);
+        }
+    }
+
+    #[test]
+    fn too_many_requests_gives_the_time_to_wait() {
+        let response = Response::builder()
+            .status(StatusCode::TOO_MANY_REQUESTS)
+            .header(RETRY_AFTER, " 120 ")
+            .body(Ok(Vec::new()))
+            .unwrap();
+        let error = resolve::<IgnoredAny>(response).unwrap_err();
+        assert_eq!(
+            error,
+            APIError::TooManyRequests(Some(Duration::from_secs(120)))
+        );
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(120)));
+        assert_eq!(
+            error.to_string(),
+            "Too many attempts, please try again in 120 seconds"
+        );
+    }
+
+    #[test]
+    fn retry_after_can_be_a_date() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        let header = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(RETRY_AFTER, value.parse().unwrap());
+            retry_after(&headers, now)
+        };
+        let later = httpdate::fmt_http_date(now + Duration::from_secs(90));
+        assert_eq!(later, "Sun, 13 Sep 2020 12:28:10 GMT");
+        assert_eq!(header(&later), Some(Duration::from_secs(90)));
+        let earlier = httpdate::fmt_http_date(now - Duration::from_secs(90));
+        assert_eq!(header(&earlier), Some(Duration::from_secs(0)));
+        assert_eq!(header("soon"), None);
+    }
+
+    #[test]
+    fn failed_requests() {
+        let network_error = Response::builder().body(Nothing.into()).unwrap();
+        let result = resolve::<IgnoredAny>(network_error);
+        assert_eq!(result.unwrap_err(), APIError::RequestError);
+        let not_utf8 = Response::builder()
+            .status(StatusCode::OK)
+            .body(Ok(vec![0xFF, 0xFE]))
+            .unwrap();
+        let result = resolve::<IgnoredAny>(not_utf8);
+        assert_eq!(result.unwrap_err(), APIError::DeserializeError);
+    }
+}
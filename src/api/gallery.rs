@@ -1,17 +1,22 @@
+use crate::api::cache::ResponseCache;
 use crate::api::error::APIError;
 use crate::api::multipart::{Multipart, MultipartFile};
-use crate::api::{APIClient, FormUrlEncoded};
+use crate::api::transport::RequestTask;
+use crate::api::upload::{upload_in_chunks, CHUNK_SIZE};
+use crate::api::{APIClient, Empty, FormUrlEncoded, RequestOptions};
 use crate::loader_task::LoadingFunction;
 use enum_iterator::IntoEnumIterator;
 use http::Method;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt::Formatter;
+use std::time::Duration;
 use url::Url;
-use yew::services::fetch::FetchTask;
 use yew::services::reader::FileData;
 use yew::Callback;
 
+const UPLOAD_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
 #[derive(Debug, Deserialize, Serialize, IntoEnumIterator, Clone, PartialEq, Eq, Hash)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum Category {
@@ -38,12 +43,15 @@ impl Category {
     }
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct GalleryItemResponse {
     pub id: u32,
     pub description: String,
     pub category: Category,
     pub files: Vec<GalleryFileResponse>,
+    // Incremented by the API on every update, if it supports it
+    #[serde(default)]
+    pub version: Option<u32>,
 }
 
 impl GalleryItemResponse {
@@ -65,7 +73,7 @@ impl GalleryItemResponse {
     }
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct GalleryFileResponse {
     pub url: Url,
     pub height: u32,
@@ -76,12 +84,39 @@ pub struct GalleryFileResponse {
 pub type GalleryListResponse = HashMap<Category, Vec<GalleryItemResponse>>;
 
 impl APIClient {
+    // on_refresh is called if the list given from the cache has changed since
     pub fn gallery_list(
         &self,
         loader: LoadingFunction,
+        on_refresh: Callback<()>,
         callback: Callback<Result<GalleryListResponse, APIError>>,
-    ) -> FetchTask {
-        self.get("gallery/list", vec![], Some(loader), callback)
+    ) -> RequestTask {
+        // The list contains every item, so they don't need to be fetched again to be edited
+        let cache = self.cache.clone();
+        let generation = cache.generation("gallery/list");
+        let callback = Callback::from(move |r: Result<GalleryListResponse, APIError>| {
+            match &r {
+                // Unless an item has been changed since the list was requested
+                Ok(list) if !cache.is_stale("gallery/list", generation) => {
+                    for item in list.values().flatten() {
+                        let key = ResponseCache::key(&gallery_item_path(item.id), &[]);
+                        cache.prime(key, serde_json::to_vec(item).unwrap());
+                    }
+                }
+                _ => {}
+            }
+            callback.emit(r);
+        });
+        self.request_with_options(
+            "gallery/list",
+            vec![],
+            Method::GET,
+            Empty,
+            RequestOptions::default()
+                .loader(Some(loader))
+                .on_refresh(on_refresh),
+            callback,
+        )
     }
 
     pub fn gallery_create(
@@ -91,7 +126,24 @@ impl APIClient {
         category: &Category,
         loader: LoadingFunction,
         callback: Callback<Result<(), APIError>>,
-    ) -> FetchTask {
+    ) -> RequestTask {
+        // Large images are sent in chunks, so a dropped connection doesn't restart the upload
+        // Only when enabled in the config, as not every API has the endpoints this needs
+        if self.chunked_uploads && image.content.len() > CHUNK_SIZE {
+            let finalize = vec![
+                ("description", description),
+                ("category", category.serialize()),
+            ];
+            return upload_in_chunks(
+                self,
+                "gallery/uploads",
+                image.name.clone(),
+                image.content.clone(),
+                finalize,
+                loader,
+                callback,
+            );
+        }
         let mut form = Multipart::new();
         form.add_text("description", description);
         form.add_text("category", category.serialize());
@@ -100,40 +152,68 @@ impl APIClient {
             image.content.clone(),
             Some(image.name.clone()),
         ));
-        self.request(
-            "gallery",
-            vec![],
-            Method::POST,
-            form,
-            Some(loader),
-            callback,
-        )
+        // Large images can take a long time to upload on a slow connection
+        let options = RequestOptions::default()
+            .timeout(Some(UPLOAD_TIMEOUT))
+            .upload_progress(true)
+            .loader(Some(loader));
+        self.request_with_options("gallery", vec![], Method::POST, form, options, callback)
     }
     pub fn gallery_update(
         &self,
         id: u32,
         description: String,
         category: Category,
-        move_after_id: Option<u32>,
-        move_to_front: bool,
+        version: Option<u32>,
         loader: LoadingFunction,
         callback: Callback<Result<(), APIError>>,
-    ) -> FetchTask {
+    ) -> RequestTask {
         let mut body = HashMap::new();
         body.insert("description", description);
         body.insert("category", category.serialize());
+        body.insert("move_to_front", false.to_string());
+        self.gallery_put(id, body, version, loader, callback)
+    }
+
+    // Moves the item after another in its category, or to the front if there isn't one. The rest
+    // of the item is sent as it was listed, so it fails with a conflict if that is out of date.
+    pub fn gallery_move(
+        &self,
+        item: &GalleryItemResponse,
+        move_after_id: Option<u32>,
+        loader: LoadingFunction,
+        callback: Callback<Result<(), APIError>>,
+    ) -> RequestTask {
+        let mut body = HashMap::new();
+        body.insert("description", item.description.clone());
+        body.insert("category", item.category.serialize());
         match move_after_id {
             None => {}
             Some(id) => {
                 body.insert("move_after_id", id.to_string());
             }
         }
-        body.insert("move_to_front", move_to_front.to_string());
-        self.put(
-            &format!("gallery/{}", id),
+        body.insert("move_to_front", move_after_id.is_none().to_string());
+        self.gallery_put(item.id, body, item.version, loader, callback)
+    }
+
+    fn gallery_put(
+        &self,
+        id: u32,
+        body: HashMap<&str, String>,
+        version: Option<u32>,
+        loader: LoadingFunction,
+        callback: Callback<Result<(), APIError>>,
+    ) -> RequestTask {
+        self.request_with_options(
+            &gallery_item_path(id),
             vec![],
+            Method::PUT,
             FormUrlEncoded(body),
-            Some(loader),
+            RequestOptions::default()
+                .if_match(version)
+                .queue_offline(format!("Changes to gallery item {}", id))
+                .loader(Some(loader)),
             callback,
         )
     }
@@ -143,8 +223,8 @@ impl APIClient {
         id: u32,
         loader: LoadingFunction,
         callback: Callback<Result<GalleryItemResponse, APIError>>,
-    ) -> FetchTask {
-        self.get(&format!("gallery/{}", id), vec![], Some(loader), callback)
+    ) -> RequestTask {
+        self.get(&gallery_item_path(id), vec![], Some(loader), callback)
     }
 
     pub fn gallery_delete(
@@ -152,7 +232,11 @@ impl APIClient {
         id: u32,
         loader: LoadingFunction,
         callback: Callback<Result<(), APIError>>,
-    ) -> FetchTask {
-        self.delete(&format!("gallery/{}", id), vec![], Some(loader), callback)
+    ) -> RequestTask {
+        self.delete(&gallery_item_path(id), vec![], Some(loader), callback)
     }
 }
+
+fn gallery_item_path(id: u32) -> String {
+    format!("gallery/{}", id)
+}
@@ -1,48 +1,149 @@
+use crate::api::cache::ResponseCache;
 use crate::api::error::resolve;
+use crate::api::outbox::OutboxEntry;
+use crate::api::retry::{is_idempotent, send_with_retry};
+use crate::api::timer::{TimeoutTimer, Timer};
+use crate::api::transport::{CompletedTask, FetchTransport, RequestTask, Transport};
+use crate::api::upload::UploadSessions;
 use crate::loader_task::{BoxedLoadingTask, LoadingFunction, LoadingTaskConfig};
-use headers::authorization::Basic;
-use headers::{Authorization, ContentType, HeaderMapExt};
-use http::{Method, Request, Response};
+use headers::authorization::{Basic, Bearer};
+use headers::{Authorization, ContentRange, ContentType, HeaderMapExt};
+use http::header::{IF_MATCH, IF_NONE_MATCH};
+use http::{Method, Request, Response, StatusCode};
+use serde::de::IgnoredAny;
 use serde::{Deserialize, Serialize};
+use std::rc::Rc;
 use std::str::FromStr;
+use std::time::Duration;
 use url::Url;
 use yew::format::{Binary, Nothing};
-use yew::services::fetch::{FetchService, FetchTask};
 use yew::Callback;
 
+mod cache;
 pub mod error;
 pub mod gallery;
 mod multipart;
+pub mod outbox;
 pub mod password_reset;
+mod retry;
 pub mod session;
+pub mod timer;
+pub mod transport;
+pub mod upload;
 pub mod users;
+mod xhr;
 
-#[derive(PartialEq, Clone)]
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+#[derive(Clone)]
 pub struct APIClient {
     base_url: Url,
-    auth_header: Option<Authorization<Basic>>,
+    auth_scheme: AuthScheme,
+    auth_header: Option<AuthHeader>,
+    user_id: Option<u32>, // Who the auth header belongs to
+    transport: Rc<dyn Transport>,
+    timer: Rc<dyn Timer>,
+    max_retries: u32,
+    chunked_uploads: bool,
+    on_unauthorized: Option<Callback<()>>,
+    on_outbox_change: Option<Callback<()>>,
+    cache: Rc<ResponseCache>,
+    uploads: Rc<UploadSessions>,
+}
+
+impl PartialEq for APIClient {
+    fn eq(&self, other: &Self) -> bool {
+        self.base_url == other.base_url
+            && self.auth_scheme == other.auth_scheme
+            && self.auth_header == other.auth_header
+            && self.user_id == other.user_id
+            && self.max_retries == other.max_retries
+            && self.chunked_uploads == other.chunked_uploads
+            && self.on_unauthorized == other.on_unauthorized
+            && self.on_outbox_change == other.on_outbox_change
+            && Rc::ptr_eq(&self.transport, &other.transport)
+            && Rc::ptr_eq(&self.timer, &other.timer)
+            && Rc::ptr_eq(&self.cache, &other.cache)
+            && Rc::ptr_eq(&self.uploads, &other.uploads)
+    }
 }
 
 impl APIClient {
     pub fn new(base_url: &str) -> Self {
+        Self::with_transport(base_url, Rc::new(FetchTransport))
+    }
+
+    pub fn with_transport(base_url: &str, transport: Rc<dyn Transport>) -> Self {
         APIClient {
             base_url: Url::from_str(base_url).unwrap(),
+            auth_scheme: AuthScheme::default(),
             auth_header: None,
+            user_id: None,
+            transport,
+            timer: Rc::new(TimeoutTimer),
+            max_retries: DEFAULT_MAX_RETRIES,
+            chunked_uploads: false,
+            on_unauthorized: None,
+            on_outbox_change: None,
+            cache: Rc::new(ResponseCache::default()),
+            uploads: Rc::new(UploadSessions::default()),
         }
     }
 
-    pub fn auth_header(&self) -> &Option<Authorization<Basic>> {
+    // For waiting between retries and timing out requests
+    pub fn set_timer(&mut self, timer: Rc<dyn Timer>) {
+        self.timer = timer
+    }
+
+    pub fn set_max_retries(&mut self, max_retries: u32) {
+        self.max_retries = max_retries
+    }
+
+    pub fn set_chunked_uploads(&mut self, chunked_uploads: bool) {
+        self.chunked_uploads = chunked_uploads
+    }
+
+    pub fn auth_scheme(&self) -> AuthScheme {
+        self.auth_scheme
+    }
+
+    pub fn set_auth_scheme(&mut self, auth_scheme: AuthScheme) {
+        self.auth_scheme = auth_scheme
+    }
+
+    pub fn auth_header(&self) -> &Option<AuthHeader> {
         &self.auth_header
     }
 
-    pub fn set_auth_header(&mut self, a: Authorization<Basic>) {
-        self.auth_header = Some(a)
+    // Cached responses belong to the previous user, so are cleared whenever this changes
+    pub fn set_auth_header(&mut self, user_id: u32, a: AuthHeader) {
+        self.cache.clear();
+        self.auth_header = Some(a);
+        self.user_id = Some(user_id)
     }
 
     pub fn remove_auth_header(&mut self) {
-        self.auth_header = None
+        self.cache.clear();
+        self.auth_header = None;
+        self.user_id = None
+    }
+
+    pub fn user_id(&self) -> Option<u32> {
+        self.user_id
     }
 
+    // Called when an authenticated request is rejected, because the session is no longer valid
+    pub fn set_on_unauthorized(&mut self, c: Callback<()>) {
+        self.on_unauthorized = Some(c)
+    }
+
+    // Called when a change is added to the outbox, because the API couldn't be reached
+    pub fn set_on_outbox_change(&mut self, c: Callback<()>) {
+        self.on_outbox_change = Some(c)
+    }
+
+    #[inline]
     pub fn request<B, T>(
         &self,
         path: &str,
@@ -51,29 +152,164 @@ impl APIClient {
         body: B,
         loader: Option<LoadingFunction>,
         callback: Callback<Result<T, error::APIError>>,
-    ) -> FetchTask
+    ) -> RequestTask
+    where
+        B: RequestBody,
+        for<'de> T: Deserialize<'de> + 'static,
+    {
+        let options = RequestOptions::default().loader(loader);
+        self.request_with_options(path, query, method, body, options, callback)
+    }
+
+    pub fn request_with_options<B, T>(
+        &self,
+        path: &str,
+        query: Vec<(String, String)>,
+        method: Method,
+        body: B,
+        options: RequestOptions,
+        callback: Callback<Result<T, error::APIError>>,
+    ) -> RequestTask
     where
         B: RequestBody,
         for<'de> T: Deserialize<'de> + 'static,
     {
-        let loader_task: Option<BoxedLoadingTask> = loader.map(|x| x(LoadingTaskConfig::default()));
+        let max_retries = if is_idempotent(&method) || options.retry_non_idempotent {
+            self.max_retries
+        } else {
+            0
+        };
+        let is_get = method == Method::GET;
+        let queue_offline = match (options.queue_offline, self.user_id) {
+            (Some(d), Some(u)) if is_idempotent(&method) && !is_get => Some((u, d)),
+            _ => None,
+        };
+        let content_type = body.content_type();
+        let cache_key = ResponseCache::key(path, &query);
+        let use_cache = is_get && options.cache;
+        let cached = if use_cache {
+            self.cache.get(&cache_key)
+        } else {
+            None
+        };
         let mut url = self.base_url.join(path).unwrap();
-        url.query_pairs_mut().extend_pairs(query);
+        url.query_pairs_mut().extend_pairs(query.iter());
         let mut builder = Request::builder().method(method).uri(url.as_str());
-        match body.content_type() {
-            Some(c) => builder.headers_mut().unwrap().typed_insert(c),
-            None => {}
+        if let Some(c) = &content_type {
+            builder.headers_mut().unwrap().typed_insert(c.clone())
+        }
+        if let Some(v) = options.if_match {
+            let etag = format!("\"{}\"", v);
+            builder = builder.header(IF_MATCH, etag);
+        }
+        if let Some(r) = options.content_range {
+            builder.headers_mut().unwrap().typed_insert(r)
         }
         match self.auth_header.as_ref() {
-            Some(a) => builder.headers_mut().unwrap().typed_insert(a.clone()),
+            Some(AuthHeader::Basic(a)) => builder.headers_mut().unwrap().typed_insert(a.clone()),
+            Some(AuthHeader::Bearer(a)) => builder.headers_mut().unwrap().typed_insert(a.clone()),
             None => {}
         }
-        let request: Binary = body.into();
-        let handler = move |response: Response<Binary>| {
-            drop(loader_task);
-            callback.emit(resolve(response));
+        if let Some(etag) = cached.as_ref().and_then(|c| c.etag.as_ref()) {
+            builder = builder.header(IF_NONE_MATCH, etag.clone())
+        }
+        let request = builder.body(body.into()).unwrap();
+        let if_match = options.if_match;
+        let outbox_entry = queue_offline.map(|(user_id, description)| {
+            OutboxEntry::new(user_id, description, path, query, &request, if_match)
+        });
+        let on_outbox_change = self.on_outbox_change.clone();
+        let on_unauthorized = match self.auth_header {
+            Some(_) => self.on_unauthorized.clone(),
+            None => None,
+        };
+        let cache = self.cache.clone();
+        let generation = cache.generation(&cache_key);
+        let cached = match cached {
+            Some(c) => c,
+            None => {
+                let loader_task: Option<BoxedLoadingTask> =
+                    options.loader.map(|x| x(LoadingTaskConfig::default()));
+                let handler = move |response: Result<Response<Binary>, error::APIError>| {
+                    if use_cache {
+                        if let Ok(r) = &response {
+                            cache.store(cache_key, generation, r, false);
+                        }
+                    } else if !is_get {
+                        // Even a failed request may have changed something
+                        cache.invalidate(&cache_key);
+                    }
+                    match outbox_entry {
+                        Some(e) if outbox::is_unreachable(&response) => {
+                            log::info!("Couldn't reach the API, queued {}", e.description);
+                            outbox::push(e);
+                            if let Some(c) = on_outbox_change {
+                                c.emit(());
+                            }
+                            callback.emit(Err(error::APIError::Queued));
+                        }
+                        _ => callback.emit(resolve_response(response, on_unauthorized)),
+                    }
+                };
+                return send_with_retry(
+                    self.transport.clone(),
+                    self.timer.clone(),
+                    request,
+                    max_retries,
+                    options.timeout,
+                    options.upload_progress,
+                    loader_task,
+                    Callback::once(handler),
+                );
+            }
+        };
+        // The cached data is shown straight away, and fetched again in the background. If that
+        // finds it has changed, the cache is updated and on_refresh is called, so that it can be
+        // requested again. The callback is only ever called once.
+        let response = Response::builder()
+            .status(StatusCode::OK)
+            .body(Ok(cached.body.clone()))
+            .unwrap();
+        callback.emit(resolve(response));
+        if cache.take_fresh(&cache_key) {
+            return Box::new(CompletedTask);
+        }
+        let id = cache.start_revalidation(cache_key.clone());
+        let on_refresh = options.on_refresh;
+        let handler = move |response: Result<Response<Binary>, error::APIError>| {
+            if !cache.finish_revalidation(&cache_key, id) {
+                return;
+            }
+            match response {
+                Ok(r) if r.status() == StatusCode::NOT_MODIFIED => {}
+                Ok(r) if r.status().is_success() => {
+                    let changed = r.body().as_ref().ok() != Some(&cached.body);
+                    let fresh = changed && on_refresh.is_some();
+                    cache.store(cache_key, generation, &r, fresh);
+                    match on_refresh {
+                        Some(c) if changed => c.emit(()),
+                        _ => {}
+                    }
+                }
+                // If the request fails, the cached data is left in place
+                r => {
+                    if let Err(e) = resolve_response::<IgnoredAny>(r, on_unauthorized) {
+                        log::warn!("Couldn't refresh {}: {}", cache_key, e);
+                    }
+                }
+            }
         };
-        FetchService::fetch_binary(builder.body(request).unwrap(), Callback::once(handler)).unwrap()
+        // Dropping the task cancels the background request, leaving the cached data as it is
+        send_with_retry(
+            self.transport.clone(),
+            self.timer.clone(),
+            request,
+            max_retries,
+            options.timeout,
+            false,
+            None,
+            Callback::once(handler),
+        )
     }
 
     #[inline]
@@ -83,7 +319,7 @@ impl APIClient {
         query: Vec<(String, String)>,
         loader: Option<LoadingFunction>,
         callback: Callback<Result<T, error::APIError>>,
-    ) -> FetchTask
+    ) -> RequestTask
     where
         for<'de> T: Deserialize<'de> + 'static,
     {
@@ -98,7 +334,7 @@ impl APIClient {
         body: B,
         loader: Option<LoadingFunction>,
         callback: Callback<Result<T, error::APIError>>,
-    ) -> FetchTask
+    ) -> RequestTask
     where
         B: RequestBody,
         for<'de> T: Deserialize<'de> + 'static,
@@ -114,7 +350,7 @@ impl APIClient {
         body: B,
         loader: Option<LoadingFunction>,
         callback: Callback<Result<T, error::APIError>>,
-    ) -> FetchTask
+    ) -> RequestTask
     where
         B: RequestBody,
         for<'de> T: Deserialize<'de> + 'static,
@@ -129,7 +365,7 @@ impl APIClient {
         query: Vec<(String, String)>,
         loader: Option<LoadingFunction>,
         callback: Callback<Result<T, error::APIError>>,
-    ) -> FetchTask
+    ) -> RequestTask
     where
         for<'de> T: Deserialize<'de> + 'static,
     {
@@ -137,6 +373,149 @@ impl APIClient {
     }
 }
 
+// How the session token is sent to the API, which must accept the chosen scheme
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthScheme {
+    // The user id as the username, and the token as the password
+    #[default]
+    Basic,
+    // Just the token, so the user id isn't sent with every request
+    Bearer,
+}
+
+impl AuthScheme {
+    pub fn header(self, user_id: u32, token: &str) -> AuthHeader {
+        match self {
+            AuthScheme::Basic => {
+                AuthHeader::Basic(Authorization::basic(&user_id.to_string(), token))
+            }
+            AuthScheme::Bearer => AuthHeader::Bearer(
+                Authorization::bearer(token).expect("session tokens are valid bearer tokens"),
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthHeader {
+    Basic(Authorization<Basic>),
+    Bearer(Authorization<Bearer>),
+}
+
+fn resolve_response<T>(
+    response: Result<Response<Binary>, error::APIError>,
+    on_unauthorized: Option<Callback<()>>,
+) -> Result<T, error::APIError>
+where
+    for<'de> T: Deserialize<'de>,
+{
+    let result = response.and_then(resolve);
+    if let (Err(error::APIError::Unauthorized(_)), Some(c)) = (&result, on_unauthorized) {
+        c.emit(())
+    }
+    result
+}
+
+pub struct RequestOptions {
+    loader: Option<LoadingFunction>,
+    on_refresh: Option<Callback<()>>,
+    retry_non_idempotent: bool,
+    timeout: Option<Duration>,
+    if_match: Option<u32>,
+    upload_progress: bool,
+    cache: bool,
+    content_range: Option<ContentRange>,
+    queue_offline: Option<String>,
+}
+
+impl Default for RequestOptions {
+    fn default() -> Self {
+        RequestOptions {
+            loader: None,
+            on_refresh: None,
+            retry_non_idempotent: false,
+            timeout: Some(Duration::from_secs(DEFAULT_TIMEOUT_SECS)),
+            if_match: None,
+            upload_progress: false,
+            cache: true,
+            content_range: None,
+            queue_offline: None,
+        }
+    }
+}
+
+impl RequestOptions {
+    // Shows that the request is in progress, and lets the user cancel it
+    pub fn loader(self, loader: Option<LoadingFunction>) -> Self {
+        let mut x = self;
+        x.loader = loader;
+        x
+    }
+
+    // GET, PUT and DELETE requests are always retried after a temporary failure
+    // Other requests should only opt in if it is safe for the server to receive them twice
+    pub fn retry_non_idempotent(self, retry_non_idempotent: bool) -> Self {
+        let mut x = self;
+        x.retry_non_idempotent = retry_non_idempotent;
+        x
+    }
+
+    // How long to wait for each attempt, or None to wait indefinitely
+    pub fn timeout(self, timeout: Option<Duration>) -> Self {
+        let mut x = self;
+        x.timeout = timeout;
+        x
+    }
+
+    // Only apply the change if the resource is still at this version, otherwise the API responds
+    // with a conflict
+    pub fn if_match(self, version: Option<u32>) -> Self {
+        let mut x = self;
+        x.if_match = version;
+        x
+    }
+
+    // Show how much of the request body has been sent in the loader, for large uploads
+    pub fn upload_progress(self, upload_progress: bool) -> Self {
+        let mut x = self;
+        x.upload_progress = upload_progress;
+        x
+    }
+
+    // Called if a response given from the cache turns out to be out of date, once the cache has
+    // been updated. The request can then be made again to get the new data.
+    pub fn on_refresh(self, on_refresh: Callback<()>) -> Self {
+        let mut x = self;
+        x.on_refresh = Some(on_refresh);
+        x
+    }
+
+    // Whether a GET response may be answered from, and stored in, the cache
+    // Should be disabled for data which is expected to change with every request
+    pub fn cache(self, cache: bool) -> Self {
+        let mut x = self;
+        x.cache = cache;
+        x
+    }
+
+    // For a request body which is only part of a larger upload
+    pub fn content_range(self, content_range: ContentRange) -> Self {
+        let mut x = self;
+        x.content_range = Some(content_range);
+        x
+    }
+
+    // If the API can't be reached, the request is saved in the outbox to be sent once back online
+    // The callback then receives APIError::Queued. Only applies to PUT and DELETE requests, as
+    // they may be sent later without depending on the response.
+    pub fn queue_offline(self, description: String) -> Self {
+        let mut x = self;
+        x.queue_offline = Some(description);
+        x
+    }
+}
+
 pub trait RequestBody: Into<Binary> {
     fn content_type(&self) -> Option<ContentType>;
 }
@@ -149,8 +528,8 @@ impl RequestBody for Empty {
     }
 }
 
-impl Into<Binary> for Empty {
-    fn into(self) -> Binary {
+impl From<Empty> for Binary {
+    fn from(_: Empty) -> Self {
         Nothing.into()
     }
 }
@@ -163,9 +542,9 @@ impl<T: Serialize> RequestBody for FormUrlEncoded<T> {
     }
 }
 
-impl<T: Serialize> Into<Binary> for FormUrlEncoded<T> {
-    fn into(self) -> Binary {
-        Ok(serde_urlencoded::to_string(self.0).unwrap()).map(|x| x.into_bytes())
+impl<T: Serialize> From<FormUrlEncoded<T>> for Binary {
+    fn from(body: FormUrlEncoded<T>) -> Self {
+        Ok(serde_urlencoded::to_string(body.0).unwrap()).map(|x| x.into_bytes())
     }
 }
 
@@ -174,3 +553,444 @@ pub struct Counted<T> {
     pub total: u32,
     pub results: Vec<T>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::gallery::{Category, GalleryItemResponse};
+    use crate::api::timer::ManualTimer;
+    use crate::api::transport::MemoryTransport;
+    use crate::loader_task::LoadingTask;
+    use http::header::{AUTHORIZATION, CONTENT_RANGE, CONTENT_TYPE, RETRY_AFTER};
+    use std::cell::{Cell, RefCell};
+    use std::collections::HashMap;
+    use yew::services::reader::FileData;
+
+    struct NoLoadingTask;
+
+    impl LoadingTask for NoLoadingTask {
+        fn set_text(&self, _: Option<String>) {}
+        fn set_on_cancel(&self, _: Option<Callback<()>>) {}
+        fn set_progress(&self, _: Option<f64>) {}
+    }
+
+    fn loader() -> LoadingFunction {
+        LoadingFunction(Rc::new(|_| Box::new(NoLoadingTask)))
+    }
+
+    fn client() -> (APIClient, Rc<MemoryTransport>) {
+        let (client, transport, _) = timed_client();
+        (client, transport)
+    }
+
+    fn timed_client() -> (APIClient, Rc<MemoryTransport>, Rc<ManualTimer>) {
+        let transport = Rc::new(MemoryTransport::new());
+        let timer = Rc::new(ManualTimer::new());
+        let mut client = APIClient::with_transport("http://api.test/", transport.clone());
+        client.set_timer(timer.clone());
+        (client, transport, timer)
+    }
+
+    type Results<T> = Rc<RefCell<Vec<Result<T, error::APIError>>>>;
+
+    // Every result the callback was called with
+    fn results<T: 'static>() -> (Callback<Result<T, error::APIError>>, Results<T>) {
+        let results = Rc::new(RefCell::new(Vec::new()));
+        let callback = {
+            let results = results.clone();
+            Callback::from(move |r| results.borrow_mut().push(r))
+        };
+        (callback, results)
+    }
+
+    fn counter() -> (Callback<()>, Rc<Cell<u32>>) {
+        let count = Rc::new(Cell::new(0));
+        let callback = {
+            let count = count.clone();
+            Callback::from(move |_| count.set(count.get() + 1))
+        };
+        (callback, count)
+    }
+
+    fn header(request: &Request<Vec<u8>>, name: http::header::HeaderName) -> Option<&str> {
+        request.headers().get(name).map(|v| v.to_str().unwrap())
+    }
+
+    fn form(request: &Request<Vec<u8>>) -> HashMap<String, String> {
+        serde_urlencoded::from_bytes(request.body()).unwrap()
+    }
+
+    const USER: &str = r#"{"id": 3, "name": "Alice", "email": "alice@example.com", "version": 4}"#;
+    const USERS: &str =
+        r#"{"total": 1, "results": [{"id": 3, "name": "Alice", "email": "alice@example.com"}]}"#;
+    const RENAMED_USERS: &str =
+        r#"{"total": 1, "results": [{"id": 3, "name": "Alicia", "email": "alice@example.com"}]}"#;
+
+    fn names(results: &[Result<Counted<users::UserResponseItem>, error::APIError>]) -> Vec<String> {
+        results
+            .iter()
+            .map(|r| r.as_ref().unwrap().results[0].name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn users_list_sends_paging_and_search() {
+        let (client, transport) = client();
+        transport.respond(StatusCode::OK, USERS);
+        let (callback, results) = results();
+        let search = Some("alice smith".to_string());
+        client.users_list(10, 20, search, loader(), Callback::noop(), callback);
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method(), Method::GET);
+        assert_eq!(
+            requests[0].uri(),
+            "http://api.test/users?limit=10&offset=20&search=alice+smith"
+        );
+        assert_eq!(header(&requests[0], AUTHORIZATION), None);
+        assert_eq!(results.borrow()[0].as_ref().unwrap().total, 1);
+    }
+
+    #[test]
+    fn users_update_sends_the_version_and_credentials() {
+        let (mut client, transport) = client();
+        client.set_auth_header(7, AuthScheme::Basic.header(7, "secret"));
+        transport.respond(StatusCode::OK, USER);
+        let (callback, results) = results();
+        let (name, email) = ("Alice".to_string(), "alice@example.com".to_string());
+        client.users_update(3, name, email, Some(4), loader(), callback);
+        let requests = transport.requests();
+        assert_eq!(requests[0].method(), Method::PUT);
+        assert_eq!(requests[0].uri(), "http://api.test/users/3");
+        assert_eq!(header(&requests[0], IF_MATCH), Some("\"4\""));
+        let credentials = format!("Basic {}", base64::encode("7:secret"));
+        assert_eq!(
+            header(&requests[0], AUTHORIZATION),
+            Some(credentials.as_str())
+        );
+        assert_eq!(
+            header(&requests[0], CONTENT_TYPE),
+            Some("application/x-www-form-urlencoded")
+        );
+        assert_eq!(form(&requests[0])["email"], "alice@example.com");
+        assert_eq!(results.borrow()[0].as_ref().unwrap().version, Some(4));
+    }
+
+    #[test]
+    fn bearer_scheme_sends_only_the_token() {
+        let (mut client, transport) = client();
+        client.set_auth_header(7, AuthScheme::Bearer.header(7, "secret"));
+        transport.respond(StatusCode::OK, "null");
+        let (callback, _) = results::<()>();
+        client.users_delete(3, loader(), callback);
+        let requests = transport.requests();
+        assert_eq!(requests[0].method(), Method::DELETE);
+        assert_eq!(requests[0].uri(), "http://api.test/users/3");
+        assert_eq!(header(&requests[0], AUTHORIZATION), Some("Bearer secret"));
+    }
+
+    #[test]
+    fn gallery_update_sends_the_form() {
+        let (client, transport) = client();
+        transport.respond(StatusCode::OK, "null");
+        let (callback, results) = results();
+        let description = "Oak staircase".to_string();
+        client.gallery_update(
+            5,
+            description,
+            Category::Staircases,
+            None,
+            loader(),
+            callback,
+        );
+        let requests = transport.requests();
+        assert_eq!(requests[0].method(), Method::PUT);
+        assert_eq!(requests[0].uri(), "http://api.test/gallery/5");
+        assert_eq!(header(&requests[0], IF_MATCH), None);
+        let form = form(&requests[0]);
+        assert_eq!(form["description"], "Oak staircase");
+        assert_eq!(form["category"], "STAIRCASES");
+        assert_eq!(form.get("move_after_id"), None);
+        assert_eq!(form["move_to_front"], "false");
+        assert_eq!(*results.borrow(), vec![Ok(())]);
+    }
+
+    #[test]
+    fn gallery_move_sends_the_listed_item_and_version() {
+        let (client, transport) = client();
+        let item =
+            r#"{"id": 5, "description": "Oak", "category": "DOORS", "files": [], "version": 3}"#;
+        let item: GalleryItemResponse = serde_json::from_str(item).unwrap();
+        transport.respond(StatusCode::OK, "null");
+        client.gallery_move(&item, Some(2), loader(), Callback::noop());
+        transport.respond(StatusCode::OK, "null");
+        client.gallery_move(&item, None, loader(), Callback::noop());
+        let requests = transport.requests();
+        assert_eq!(requests[0].uri(), "http://api.test/gallery/5");
+        assert_eq!(header(&requests[0], IF_MATCH), Some("\"3\""));
+        let form_after = form(&requests[0]);
+        assert_eq!(form_after["description"], "Oak");
+        assert_eq!(form_after["category"], "DOORS");
+        assert_eq!(form_after["move_after_id"], "2");
+        assert_eq!(form_after["move_to_front"], "false");
+        let form_front = form(&requests[1]);
+        assert_eq!(form_front.get("move_after_id"), None);
+        assert_eq!(form_front["move_to_front"], "true");
+    }
+
+    fn image(bytes: usize) -> FileData {
+        FileData {
+            name: "stairs.jpg".to_string(),
+            content: vec![0; bytes],
+        }
+    }
+
+    #[test]
+    fn gallery_create_sends_large_images_in_one_request_by_default() {
+        let (client, transport) = client();
+        transport.respond(StatusCode::OK, "null");
+        let (callback, results) = results();
+        let description = "Oak staircase".to_string();
+        client.gallery_create(
+            &image(upload::CHUNK_SIZE * 2),
+            description,
+            &Category::Doors,
+            loader(),
+            callback,
+        );
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method(), Method::POST);
+        assert_eq!(requests[0].uri(), "http://api.test/gallery");
+        let content_type = header(&requests[0], CONTENT_TYPE).unwrap();
+        assert!(content_type.starts_with("multipart/form-data; boundary="));
+        assert_eq!(*results.borrow(), vec![Ok(())]);
+    }
+
+    #[test]
+    fn gallery_create_sends_large_images_in_chunks_when_enabled() {
+        let (mut client, transport) = client();
+        client.set_chunked_uploads(true);
+        let size = upload::CHUNK_SIZE + 10;
+        transport.respond(StatusCode::OK, r#"{"id": "u1", "received": 0}"#);
+        let received = format!(r#"{{"id": "u1", "received": {}}}"#, upload::CHUNK_SIZE);
+        transport.respond(StatusCode::OK, &received);
+        let received = format!(r#"{{"id": "u1", "received": {}}}"#, size);
+        transport.respond(StatusCode::OK, &received);
+        transport.respond(StatusCode::OK, "null");
+        let (callback, results) = results();
+        let description = "Oak staircase".to_string();
+        client.gallery_create(
+            &image(size),
+            description,
+            &Category::Doors,
+            loader(),
+            callback,
+        );
+        let requests = transport.requests();
+        let sent: Vec<_> = requests
+            .iter()
+            .map(|r| (r.method().as_str(), r.uri().path(), r.body().len()))
+            .collect();
+        assert_eq!(
+            sent,
+            vec![
+                ("POST", "/gallery/uploads", sent[0].2),
+                ("PUT", "/gallery/uploads/u1", upload::CHUNK_SIZE),
+                ("PUT", "/gallery/uploads/u1", 10),
+                ("POST", "/gallery/uploads/u1/finalize", sent[3].2),
+            ]
+        );
+        let range = format!("bytes {}-{}/{}", upload::CHUNK_SIZE, size - 1, size);
+        assert_eq!(header(&requests[2], CONTENT_RANGE), Some(range.as_str()));
+        assert_eq!(form(&requests[3])["category"], "DOORS");
+        assert_eq!(*results.borrow(), vec![Ok(())]);
+    }
+
+    #[test]
+    fn posts_are_only_retried_when_opted_in() {
+        let (client, transport, timer) = timed_client();
+        transport.respond(StatusCode::SERVICE_UNAVAILABLE, "");
+        let (callback, failed) = results::<()>();
+        let options = RequestOptions::default();
+        client.request_with_options("gallery", vec![], Method::POST, Empty, options, callback);
+        assert_eq!(transport.requests().len(), 1);
+        assert!(!timer.fire());
+        let error = failed.borrow()[0].clone().unwrap_err();
+        assert_eq!(
+            error,
+            error::APIError::UnknownError(StatusCode::SERVICE_UNAVAILABLE)
+        );
+
+        transport.respond(StatusCode::SERVICE_UNAVAILABLE, "");
+        transport.respond(StatusCode::OK, "null");
+        let (callback, retried) = results::<()>();
+        let options = RequestOptions::default().retry_non_idempotent(true);
+        // Dropping the task would cancel the retry
+        let _task =
+            client.request_with_options("gallery", vec![], Method::POST, Empty, options, callback);
+        assert_eq!(transport.requests().len(), 2);
+        assert!(retried.borrow().is_empty());
+        assert!(timer.fire());
+        assert_eq!(transport.requests().len(), 3);
+        assert_eq!(*retried.borrow(), vec![Ok(())]);
+    }
+
+    #[test]
+    fn login_is_not_retried() {
+        let (mut client, transport) = client();
+        client.set_max_retries(3);
+        transport.respond(StatusCode::SERVICE_UNAVAILABLE, "");
+        let (callback, results) = results();
+        let (email, password) = ("alice@example.com".to_string(), "hunter2".to_string());
+        client.session_login(email, password, loader(), callback);
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].uri(), "http://api.test/sessions/login");
+        assert_eq!(form(&requests[0])["password"], "hunter2");
+        let error = results.borrow()[0].clone().unwrap_err();
+        assert_eq!(
+            error,
+            error::APIError::UnknownError(StatusCode::SERVICE_UNAVAILABLE)
+        );
+    }
+
+    #[test]
+    fn login_lockout_gives_the_time_to_wait() {
+        let (client, transport) = client();
+        let response = Response::builder()
+            .status(StatusCode::TOO_MANY_REQUESTS)
+            .header(RETRY_AFTER, "30")
+            .body(Ok(b"{\"code\": \"TOO_MANY_ATTEMPTS\"}".to_vec()))
+            .unwrap();
+        transport.respond_with(response);
+        let (callback, results) = results();
+        let (email, password) = ("alice@example.com".to_string(), "wrong".to_string());
+        client.session_login(email, password, loader(), callback);
+        let error = results.borrow()[0].clone().unwrap_err();
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn rejected_credentials_sign_the_user_out() {
+        let (mut client, transport) = client();
+        let (on_unauthorized, signed_out) = counter();
+        client.set_on_unauthorized(on_unauthorized);
+        let expired = r#"{"code": "INCORRECT_CREDENTIALS"}"#;
+        transport.respond(StatusCode::UNAUTHORIZED, expired);
+        client.users_get(3, None, Callback::noop());
+        assert_eq!(signed_out.get(), 0, "only a signed in user is signed out");
+        client.set_auth_header(7, AuthScheme::Basic.header(7, "secret"));
+        transport.respond(StatusCode::UNAUTHORIZED, expired);
+        client.users_get(3, None, Callback::noop());
+        assert_eq!(signed_out.get(), 1);
+    }
+
+    #[test]
+    fn cached_data_is_given_once_then_revalidated() {
+        let (client, transport) = client();
+        let response = Response::builder()
+            .status(StatusCode::OK)
+            .header(http::header::ETAG, "\"4\"")
+            .body(Ok(USER.as_bytes().to_vec()))
+            .unwrap();
+        transport.respond_with(response);
+        let (callback, results) = results::<users::UserResponseItem>();
+        client.users_get(3, None, callback.clone());
+        transport.respond(StatusCode::NOT_MODIFIED, "");
+        client.users_get(3, None, callback);
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(header(&requests[1], IF_NONE_MATCH), Some("\"4\""));
+        let results = results.borrow();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0], results[1]);
+    }
+
+    #[test]
+    fn changed_data_is_refreshed_on_request() {
+        let (client, transport) = client();
+        let (on_refresh, refreshed) = counter();
+        let (callback, results) = results();
+        let list = |callback: &Callback<_>| {
+            client.users_list(10, 0, None, loader(), on_refresh.clone(), callback.clone())
+        };
+        transport.respond(StatusCode::OK, USERS);
+        list(&callback);
+        // The cached page is given, then the refresh finds the user has been renamed
+        transport.respond(StatusCode::OK, RENAMED_USERS);
+        list(&callback);
+        assert_eq!(refreshed.get(), 1);
+        // The refreshed page is given without fetching it again
+        list(&callback);
+        assert_eq!(transport.requests().len(), 2);
+        // Unchanged data doesn't cause another refresh
+        transport.respond(StatusCode::OK, RENAMED_USERS);
+        list(&callback);
+        assert_eq!(transport.requests().len(), 3);
+        assert_eq!(refreshed.get(), 1);
+        assert_eq!(
+            names(&results.borrow()),
+            vec!["Alice", "Alice", "Alicia", "Alicia"]
+        );
+    }
+
+    #[test]
+    fn changes_invalidate_the_cache() {
+        let (client, transport) = client();
+        let (callback, results) = results::<users::UserResponseItem>();
+        transport.respond(StatusCode::OK, USER);
+        client.users_get(3, None, callback.clone());
+        transport.respond(StatusCode::OK, USER);
+        let (name, email) = ("Alicia".to_string(), "alice@example.com".to_string());
+        client.users_update(3, name, email, Some(4), loader(), Callback::noop());
+        transport.respond(StatusCode::OK, USER);
+        client.users_get(3, None, callback);
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(header(&requests[2], IF_NONE_MATCH), None);
+        assert_eq!(results.borrow().len(), 2);
+    }
+
+    #[test]
+    fn responses_from_before_a_change_are_not_cached() {
+        let (client, transport) = client();
+        let (callback, stale) = results();
+        transport.respond(StatusCode::OK, USERS);
+        transport.hold();
+        let _list = client.users_list(10, 0, None, loader(), Callback::noop(), callback);
+        // The user is renamed while the list is loading
+        transport.respond(StatusCode::OK, USER);
+        let (name, email) = ("Alicia".to_string(), "alice@example.com".to_string());
+        client.users_update(3, name, email, Some(4), loader(), Callback::noop());
+        transport.release();
+        assert_eq!(names(&stale.borrow()), vec!["Alice"]);
+        // So the list from before is fetched again, instead of being shown from the cache
+        let (callback, results) = results();
+        transport.respond(StatusCode::OK, RENAMED_USERS);
+        client.users_list(10, 0, None, loader(), Callback::noop(), callback);
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(header(&requests[2], IF_NONE_MATCH), None);
+        assert_eq!(names(&results.borrow()), vec!["Alicia"]);
+    }
+
+    #[test]
+    fn gallery_items_are_cached_from_the_list() {
+        let (client, transport) = client();
+        let item = r#"{"id": 5, "description": "Oak", "category": "DOORS", "files": []}"#;
+        transport.respond(StatusCode::OK, &format!(r#"{{"DOORS": [{}]}}"#, item));
+        client.gallery_list(loader(), Callback::noop(), Callback::noop());
+        transport.respond(StatusCode::OK, item);
+        let (callback, results) = results();
+        client.gallery_get(5, loader(), callback);
+        // The item is given from the list straight away, and fetched in the background
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].uri(), "http://api.test/gallery/5");
+        assert_eq!(header(&requests[1], IF_NONE_MATCH), None);
+        let results = results.borrow();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].as_ref().unwrap().description, "Oak");
+    }
+}
@@ -62,8 +62,8 @@ impl RequestBody for Multipart {
     }
 }
 
-impl Into<Binary> for Multipart {
-    fn into(self) -> Binary {
+impl From<Multipart> for Binary {
+    fn from(body: Multipart) -> Self {
         let mut data = Vec::new();
 
         /* The encapsulation boundary is defined as a line consisting entirely of two hyphen
@@ -71,9 +71,9 @@ impl Into<Binary> for Multipart {
         Content-Type header field. The boundary must be followed immediately either by another
         CRLF and the header fields for the next part, or by two CRLFs, in which case there are no
         header fields for the next part (and it is therefore assumed to be of Content-Type text/plain). */
-        let encapsulation_boundary = format! {"--{}\r\n", self.boundary};
+        let encapsulation_boundary = format! {"--{}\r\n", body.boundary};
 
-        for (name, value) in self.text_fields {
+        for (name, value) in body.text_fields {
             data.extend_from_slice(encapsulation_boundary.as_bytes());
             data.append(
                 &mut format!("Content-Disposition:form-data; name=\"{}\"\r\n\r\n", name)
@@ -83,7 +83,7 @@ impl Into<Binary> for Multipart {
             data.extend_from_slice("\r\n".as_bytes());
         }
 
-        for mut file in self.files {
+        for mut file in body.files {
             data.extend_from_slice(encapsulation_boundary.as_bytes());
             let mut s = format!("Content-Disposition:form-data; name=\"{}\";", file.name);
             match file.filename {
@@ -106,7 +106,7 @@ impl Into<Binary> for Multipart {
         /* The encapsulation boundary following the last body part is a distinguished delimiter that
         indicates that no further body parts will follow. Such a delimiter is identical to the previous
         delimiters, with the addition of two more hyphens at the end of the line */
-        data.append(&mut format!("--{}--\r\n", self.boundary).into_bytes());
+        data.append(&mut format!("--{}--\r\n", body.boundary).into_bytes());
         Ok(data)
     }
 }
@@ -0,0 +1,260 @@
+use crate::api::error::APIError;
+use crate::api::transport::RequestTask;
+use crate::api::{APIClient, RequestBody, RequestOptions};
+use crate::loader_task::LoadingFunction;
+use headers::{ContentType, HeaderMapExt};
+use http::{Method, Request, Response};
+use mime::Mime;
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{Storage, StorageEvent};
+use yew::format::{Binary, Nothing};
+use yew::Callback;
+
+const KEY: &str = "kiwi_outbox";
+
+// How long a tab has to send an entry before another tab may send it instead, in case it was
+// closed while sending
+const CLAIM_MILLIS: f64 = 5.0 * 60.0 * 1000.0;
+
+// A change which couldn't be sent because the API was unreachable
+// It is kept in local storage until it can be sent again, so isn't lost if the page is closed
+// Only sent while the user who made it is signed in, otherwise it waits for them to sign in again
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct OutboxEntry {
+    pub id: u64,
+    pub user_id: u32,
+    pub description: String,
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    content_type: Option<String>,
+    body: Option<String>, // Base64 encoded
+    if_match: Option<u32>,
+    // Milliseconds since the epoch, while a tab is sending this
+    #[serde(default)]
+    claimed_until: Option<f64>,
+}
+
+impl OutboxEntry {
+    pub fn new(
+        user_id: u32,
+        description: String,
+        path: &str,
+        query: Vec<(String, String)>,
+        request: &Request<Binary>,
+        if_match: Option<u32>,
+    ) -> Self {
+        let content_type = request.headers().typed_get::<ContentType>();
+        Self {
+            id: rand::random(),
+            user_id,
+            description,
+            method: request.method().to_string(),
+            path: path.to_string(),
+            query,
+            content_type: content_type.map(|c| c.to_string()),
+            body: request.body().as_ref().ok().map(base64::encode),
+            if_match,
+            claimed_until: None,
+        }
+    }
+
+    // A later change to the same thing replaces this one
+    fn replaces(&self, other: &OutboxEntry) -> bool {
+        self.user_id == other.user_id
+            && self.method == other.method
+            && self.path == other.path
+            && self.query == other.query
+    }
+}
+
+fn storage() -> Option<Storage> {
+    web_sys::window().unwrap().local_storage().ok().flatten()
+}
+
+// The changes waiting to be sent, oldest first
+pub fn entries() -> Vec<OutboxEntry> {
+    storage()
+        .and_then(|s| s.get_item(KEY).ok().flatten())
+        .and_then(|x| serde_json::from_str(&x).ok())
+        .unwrap_or_default()
+}
+
+// The changes made by a user, oldest first
+pub fn entries_for(user_id: u32) -> Vec<OutboxEntry> {
+    let mut entries = entries();
+    entries.retain(|e| e.user_id == user_id);
+    entries
+}
+
+fn save(entries: &[OutboxEntry]) {
+    if let Some(s) = storage() {
+        let result = if entries.is_empty() {
+            s.remove_item(KEY)
+        } else {
+            s.set_item(KEY, &serde_json::to_string(entries).unwrap())
+        };
+        if result.is_err() {
+            log::error!("Couldn't save the outbox");
+        }
+    }
+}
+
+pub fn push(entry: OutboxEntry) {
+    let mut entries = entries();
+    entries.retain(|e| !entry.replaces(e));
+    entries.push(entry);
+    save(&entries);
+}
+
+// The user's oldest change, unless another tab is already sending it
+// Claiming it stops every tab which comes online from sending the same change
+pub fn claim_next(user_id: u32) -> Option<OutboxEntry> {
+    let mut entries = entries();
+    let now = js_sys::Date::now();
+    let entry = entries.iter_mut().find(|e| e.user_id == user_id)?;
+    if matches!(entry.claimed_until, Some(t) if t > now) {
+        return None;
+    }
+    entry.claimed_until = Some(now + CLAIM_MILLIS);
+    let claimed = entry.clone();
+    save(&entries);
+    Some(claimed)
+}
+
+// Lets any tab send the change again, after it couldn't be sent
+pub fn release(id: u64) {
+    let mut entries = entries();
+    for e in entries.iter_mut().filter(|e| e.id == id) {
+        e.claimed_until = None;
+    }
+    save(&entries);
+}
+
+pub fn remove(id: u64) {
+    let mut entries = entries();
+    entries.retain(|e| e.id != id);
+    save(&entries);
+}
+
+// Whether the request failed because the API couldn't be reached, rather than being rejected
+pub fn is_unreachable(response: &Result<Response<Binary>, APIError>) -> bool {
+    match response {
+        Ok(r) => r.body().is_err(),
+        Err(e) => *e == APIError::Timeout,
+    }
+}
+
+struct StoredBody {
+    content_type: Option<ContentType>,
+    data: Binary,
+}
+
+impl RequestBody for StoredBody {
+    fn content_type(&self) -> Option<ContentType> {
+        self.content_type.clone()
+    }
+}
+
+impl From<StoredBody> for Binary {
+    fn from(body: StoredBody) -> Self {
+        body.data
+    }
+}
+
+impl APIClient {
+    // Sends a queued change again, it isn't queued a second time if it still can't be sent
+    pub fn outbox_replay(
+        &self,
+        entry: &OutboxEntry,
+        loader: Option<LoadingFunction>,
+        callback: Callback<Result<(), APIError>>,
+    ) -> RequestTask {
+        let method = entry.method.parse().unwrap_or(Method::PUT);
+        let content_type = entry
+            .content_type
+            .as_ref()
+            .and_then(|c| c.parse::<Mime>().ok())
+            .map(ContentType::from);
+        let data = match entry.body.as_ref().map(base64::decode) {
+            Some(Ok(b)) => Ok(b),
+            _ => Nothing.into(),
+        };
+        let body = StoredBody { content_type, data };
+        // The response is whatever the original request returned, which isn't needed any more
+        let callback = Callback::from(move |r: Result<IgnoredAny, APIError>| {
+            callback.emit(r.map(|_| ()));
+        });
+        self.request_with_options(
+            &entry.path,
+            entry.query.clone(),
+            method,
+            body,
+            RequestOptions::default()
+                .if_match(entry.if_match)
+                .loader(loader),
+            callback,
+        )
+    }
+}
+
+// Notifies when another tab changes the outbox
+pub struct OutboxWatcher {
+    listener: Closure<dyn FnMut(StorageEvent)>,
+}
+
+impl OutboxWatcher {
+    pub fn new(callback: Callback<()>) -> Self {
+        let f: Box<dyn FnMut(StorageEvent)> = Box::new(move |e: StorageEvent| {
+            // A key of None means the whole storage was cleared
+            match e.key() {
+                Some(k) if k != KEY => {}
+                _ => callback.emit(()),
+            }
+        });
+        let listener = Closure::wrap(f);
+        web_sys::window()
+            .unwrap()
+            .add_event_listener_with_callback("storage", listener.as_ref().unchecked_ref())
+            .unwrap();
+        Self { listener }
+    }
+}
+
+impl Drop for OutboxWatcher {
+    fn drop(&mut self) {
+        web_sys::window()
+            .unwrap()
+            .remove_event_listener_with_callback("storage", self.listener.as_ref().unchecked_ref())
+            .unwrap();
+    }
+}
+
+// Notifies when the browser regains its network connection
+pub struct OnlineWatcher {
+    listener: Closure<dyn FnMut()>,
+}
+
+impl OnlineWatcher {
+    pub fn new(callback: Callback<()>) -> Self {
+        let f: Box<dyn FnMut()> = Box::new(move || callback.emit(()));
+        let listener = Closure::wrap(f);
+        web_sys::window()
+            .unwrap()
+            .add_event_listener_with_callback("online", listener.as_ref().unchecked_ref())
+            .unwrap();
+        Self { listener }
+    }
+}
+
+impl Drop for OnlineWatcher {
+    fn drop(&mut self) {
+        web_sys::window()
+            .unwrap()
+            .remove_event_listener_with_callback("online", self.listener.as_ref().unchecked_ref())
+            .unwrap();
+    }
+}
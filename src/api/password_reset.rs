@@ -1,8 +1,8 @@
 use crate::api::error::APIError;
+use crate::api::transport::RequestTask;
 use crate::api::{APIClient, FormUrlEncoded};
 use crate::loader_task::LoadingFunction;
 use std::collections::HashMap;
-use yew::services::fetch::FetchTask;
 use yew::Callback;
 
 impl APIClient {
@@ -11,7 +11,7 @@ impl APIClient {
         email: String,
         loader: LoadingFunction,
         callback: Callback<Result<(), APIError>>,
-    ) -> FetchTask {
+    ) -> RequestTask {
         let mut body = HashMap::new();
         body.insert("email", email);
         self.post(
@@ -29,7 +29,7 @@ impl APIClient {
         new_password: String,
         loader: LoadingFunction,
         callback: Callback<Result<(), APIError>>,
-    ) -> FetchTask {
+    ) -> RequestTask {
         let mut body = HashMap::new();
         body.insert("email", email);
         body.insert("token", token);
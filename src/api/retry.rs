@@ -0,0 +1,423 @@
+use crate::api::error::APIError;
+use crate::api::timer::Timer;
+use crate::api::transport::{RequestTask, Transport, UploadProgress};
+use crate::loader_task::BoxedLoadingTask;
+use http::{HeaderMap, Method, Request, Response, StatusCode};
+use rand::Rng;
+use std::cell::{Cell, RefCell};
+use std::cmp::min;
+use std::rc::{Rc, Weak};
+use std::time::Duration;
+use yew::format::{Binary, Nothing};
+use yew::services::Task;
+use yew::Callback;
+
+const INITIAL_BACKOFF_MILLIS: u64 = 500;
+const MAX_BACKOFF_MILLIS: u64 = 8000;
+
+// Sends a request, retrying with exponential backoff if it fails in a way that may be temporary
+// Each attempt is abandoned if it takes longer than the timeout
+// Dropping the returned task cancels the request, including any pending retry
+// The user can also cancel the request from the loader, the callback then receives an error
+#[allow(clippy::too_many_arguments)]
+pub fn send_with_retry(
+    transport: Rc<dyn Transport>,
+    timer: Rc<dyn Timer>,
+    request: Request<Binary>,
+    max_retries: u32,
+    timeout: Option<Duration>,
+    upload_progress: bool,
+    loader_task: Option<BoxedLoadingTask>,
+    callback: Callback<Result<Response<Binary>, APIError>>,
+) -> RequestTask {
+    let (parts, body) = request.into_parts();
+    let state = Rc::new(RetryState {
+        transport,
+        timer,
+        method: parts.method,
+        uri: parts.uri.to_string(),
+        headers: parts.headers,
+        body,
+        max_retries,
+        timeout,
+        upload_progress,
+        attempt: Cell::new(0),
+        finished: Cell::new(false),
+        loader_task: RefCell::new(loader_task),
+        callback,
+        current: RefCell::new(None),
+        attempt_timeout: RefCell::new(None),
+    });
+    if let Some(l) = state.loader_task.borrow().as_ref() {
+        let weak = Rc::downgrade(&state);
+        l.set_on_cancel(Some(Callback::from(move |_| {
+            if let Some(s) = weak.upgrade() {
+                s.finish(Err(APIError::Cancelled));
+            }
+        })));
+    }
+    state.send();
+    Box::new(RetryTask(state))
+}
+
+struct RetryState {
+    transport: Rc<dyn Transport>,
+    timer: Rc<dyn Timer>,
+    method: Method,
+    uri: String,
+    headers: HeaderMap,
+    body: Binary,
+    max_retries: u32,
+    timeout: Option<Duration>,
+    upload_progress: bool,
+    attempt: Cell<u32>,
+    finished: Cell<bool>,
+    loader_task: RefCell<Option<BoxedLoadingTask>>,
+    callback: Callback<Result<Response<Binary>, APIError>>,
+    // Either the in flight request, or the timer for the next attempt
+    current: RefCell<Option<Box<dyn Task>>>,
+    attempt_timeout: RefCell<Option<Box<dyn Task>>>,
+}
+
+impl RetryState {
+    fn send(self: &Rc<Self>) {
+        let mut builder = Request::builder()
+            .method(self.method.clone())
+            .uri(self.uri.as_str());
+        *builder.headers_mut().unwrap() = self.headers.clone();
+        let body = match &self.body {
+            Ok(b) => Ok(b.clone()),
+            Err(_) => Nothing.into(),
+        };
+        let weak = Rc::downgrade(self);
+        let attempt = self.attempt.get();
+        let callback = Callback::once(move |response| {
+            if let Some(s) = weak.upgrade() {
+                s.on_result(Ok(response));
+            }
+        });
+        let request = builder.body(body).unwrap();
+        let task = if self.upload_progress {
+            let weak = Rc::downgrade(self);
+            let progress = Callback::from(move |p| {
+                if let Some(s) = weak.upgrade() {
+                    s.on_progress(p);
+                }
+            });
+            self.transport
+                .send_with_progress(request, progress, callback)
+        } else {
+            self.transport.send(request, callback)
+        };
+        // The transport may already have responded, and scheduled a retry or finished
+        if self.finished.get() || self.attempt.get() != attempt {
+            return;
+        }
+        *self.current.borrow_mut() = Some(task);
+        if let Some(t) = self.timeout {
+            let weak = Rc::downgrade(self);
+            *self.attempt_timeout.borrow_mut() = Some(self.timer.spawn(
+                t,
+                Callback::from(move |_| {
+                    if let Some(s) = weak.upgrade() {
+                        s.on_result(Err(APIError::Timeout));
+                    }
+                }),
+            ));
+        }
+    }
+
+    fn on_progress(&self, progress: UploadProgress) {
+        if let Some(l) = self.loader_task.borrow().as_ref() {
+            let fraction = progress.fraction();
+            l.set_progress(Some(fraction));
+            l.set_text(Some(format!("Uploading… {}%", (fraction * 100.0) as u32)));
+        }
+    }
+
+    fn on_result(self: &Rc<Self>, result: Result<Response<Binary>, APIError>) {
+        let previous = self.attempt_timeout.borrow_mut().take();
+        drop(previous);
+        let attempt = self.attempt.get() + 1;
+        let temporary = match &result {
+            Ok(r) => is_temporary_failure(r),
+            Err(e) => *e == APIError::Timeout,
+        };
+        if attempt <= self.max_retries && temporary {
+            self.attempt.set(attempt);
+            if let Some(l) = self.loader_task.borrow().as_ref() {
+                l.set_text(Some(format!(
+                    "Retrying ({}/{})…",
+                    attempt, self.max_retries
+                )));
+            }
+            let weak: Weak<Self> = Rc::downgrade(self);
+            let timeout = self.timer.spawn(
+                backoff(attempt),
+                Callback::from(move |_| {
+                    if let Some(s) = weak.upgrade() {
+                        s.send();
+                    }
+                }),
+            );
+            let previous = self.current.replace(Some(timeout));
+            drop(previous);
+        } else {
+            self.finish(result);
+        }
+    }
+
+    fn finish(&self, result: Result<Response<Binary>, APIError>) {
+        if self.finished.replace(true) {
+            return;
+        }
+        self.cancel();
+        let loader_task = self.loader_task.borrow_mut().take();
+        drop(loader_task);
+        self.callback.emit(result);
+    }
+
+    fn cancel(&self) {
+        let current = self.current.borrow_mut().take();
+        drop(current);
+        let attempt_timeout = self.attempt_timeout.borrow_mut().take();
+        drop(attempt_timeout);
+    }
+}
+
+struct RetryTask(Rc<RetryState>);
+
+impl Task for RetryTask {
+    fn is_active(&self) -> bool {
+        !self.0.finished.get()
+    }
+}
+
+impl Drop for RetryTask {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+pub fn is_idempotent(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::PUT | Method::DELETE)
+}
+
+fn is_temporary_failure(response: &Response<Binary>) -> bool {
+    response.body().is_err()
+        || matches!(
+            response.status(),
+            StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+        )
+}
+
+// Exponential backoff, with jitter so that clients don't all retry at the same moment
+fn backoff(attempt: u32) -> Duration {
+    let max = min(
+        INITIAL_BACKOFF_MILLIS.saturating_mul(1 << min(attempt - 1, 16)),
+        MAX_BACKOFF_MILLIS,
+    );
+    let millis = rand::thread_rng().gen_range(max / 2, max + 1);
+    Duration::from_millis(millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::timer::ManualTimer;
+    use crate::api::transport::MemoryTransport;
+    use crate::loader_task::LoadingTask;
+
+    fn response(status: StatusCode) -> Response<Binary> {
+        Response::builder()
+            .status(status)
+            .body(Ok(Vec::new()))
+            .unwrap()
+    }
+
+    fn get() -> Request<Binary> {
+        Request::get("http://api.test/users")
+            .body(Nothing.into())
+            .unwrap()
+    }
+
+    // Keeps every text the loader is given
+    struct RecordingLoadingTask(Rc<RefCell<Vec<String>>>);
+
+    impl LoadingTask for RecordingLoadingTask {
+        fn set_text(&self, text: Option<String>) {
+            self.0.borrow_mut().extend(text);
+        }
+        fn set_on_cancel(&self, _: Option<Callback<()>>) {}
+        fn set_progress(&self, _: Option<f64>) {}
+    }
+
+    type Statuses = Rc<RefCell<Vec<StatusCode>>>;
+
+    fn statuses() -> (Callback<Result<Response<Binary>, APIError>>, Statuses) {
+        let statuses = Rc::new(RefCell::new(Vec::new()));
+        let callback = {
+            let statuses = statuses.clone();
+            Callback::from(move |r: Result<Response<Binary>, APIError>| {
+                statuses.borrow_mut().push(r.unwrap().status());
+            })
+        };
+        (callback, statuses)
+    }
+
+    #[test]
+    fn only_requests_without_side_effects_are_idempotent() {
+        assert!(is_idempotent(&Method::GET));
+        assert!(is_idempotent(&Method::PUT));
+        assert!(is_idempotent(&Method::DELETE));
+        assert!(!is_idempotent(&Method::POST));
+        assert!(!is_idempotent(&Method::PATCH));
+    }
+
+    #[test]
+    fn temporary_failures() {
+        let network_error = Response::builder().body(Nothing.into()).unwrap();
+        assert!(is_temporary_failure(&network_error));
+        assert!(is_temporary_failure(&response(StatusCode::BAD_GATEWAY)));
+        assert!(is_temporary_failure(&response(
+            StatusCode::SERVICE_UNAVAILABLE
+        )));
+        assert!(is_temporary_failure(&response(StatusCode::GATEWAY_TIMEOUT)));
+        assert!(!is_temporary_failure(&response(StatusCode::OK)));
+        assert!(!is_temporary_failure(&response(
+            StatusCode::INTERNAL_SERVER_ERROR
+        )));
+        assert!(!is_temporary_failure(&response(
+            StatusCode::TOO_MANY_REQUESTS
+        )));
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        for attempt in 1..40 {
+            let max = min(
+                INITIAL_BACKOFF_MILLIS << min(attempt - 1, 16),
+                MAX_BACKOFF_MILLIS,
+            );
+            let millis = backoff(attempt).as_millis() as u64;
+            assert!(
+                millis >= max / 2 && millis <= max,
+                "attempt {}: {}ms",
+                attempt,
+                millis
+            );
+        }
+    }
+
+    #[test]
+    fn temporary_failures_are_retried_after_a_backoff() {
+        let (transport, timer) = (Rc::new(MemoryTransport::new()), Rc::new(ManualTimer::new()));
+        transport.respond(StatusCode::SERVICE_UNAVAILABLE, "");
+        transport.respond(StatusCode::BAD_GATEWAY, "");
+        transport.respond(StatusCode::OK, "");
+        let texts = Rc::new(RefCell::new(Vec::new()));
+        let loader_task: BoxedLoadingTask = Box::new(RecordingLoadingTask(texts.clone()));
+        let (callback, statuses) = statuses();
+        let timeout = Some(Duration::from_secs(30));
+        let task = send_with_retry(
+            transport.clone(),
+            timer.clone(),
+            get(),
+            3,
+            timeout,
+            false,
+            Some(loader_task),
+            callback,
+        );
+        assert_eq!(transport.requests().len(), 1);
+        assert_eq!(*texts.borrow(), vec!["Retrying (1/3)…"]);
+        let waiting = timer.waiting();
+        assert_eq!(waiting.len(), 1);
+        assert!(waiting[0] >= Duration::from_millis(250) && waiting[0] <= backoff_max(1));
+
+        assert!(timer.fire());
+        assert_eq!(transport.requests().len(), 2);
+        assert_eq!(*texts.borrow(), vec!["Retrying (1/3)…", "Retrying (2/3)…"]);
+        assert!(timer.waiting()[0] <= backoff_max(2));
+        assert!(statuses.borrow().is_empty());
+
+        assert!(timer.fire());
+        assert_eq!(transport.requests().len(), 3);
+        assert_eq!(*statuses.borrow(), vec![StatusCode::OK]);
+        assert!(!task.is_active());
+        // Including the timeout for the last attempt
+        assert!(timer.waiting().is_empty());
+    }
+
+    #[test]
+    fn the_last_response_is_given_once_retries_run_out() {
+        let (transport, timer) = (Rc::new(MemoryTransport::new()), Rc::new(ManualTimer::new()));
+        for _ in 0..3 {
+            transport.respond(StatusCode::SERVICE_UNAVAILABLE, "");
+        }
+        let texts = Rc::new(RefCell::new(Vec::new()));
+        let loader_task: BoxedLoadingTask = Box::new(RecordingLoadingTask(texts.clone()));
+        let (callback, statuses) = statuses();
+        let task = send_with_retry(
+            transport.clone(),
+            timer.clone(),
+            get(),
+            2,
+            None,
+            false,
+            Some(loader_task),
+            callback,
+        );
+        while timer.fire() {}
+        assert!(!task.is_active());
+        assert_eq!(transport.requests().len(), 3);
+        assert_eq!(*texts.borrow(), vec!["Retrying (1/2)…", "Retrying (2/2)…"]);
+        assert_eq!(*statuses.borrow(), vec![StatusCode::SERVICE_UNAVAILABLE]);
+    }
+
+    #[test]
+    fn other_failures_are_not_retried() {
+        let (transport, timer) = (Rc::new(MemoryTransport::new()), Rc::new(ManualTimer::new()));
+        transport.respond(StatusCode::INTERNAL_SERVER_ERROR, "");
+        let (callback, statuses) = statuses();
+        let task = send_with_retry(
+            transport.clone(),
+            timer.clone(),
+            get(),
+            3,
+            None,
+            false,
+            None,
+            callback,
+        );
+        assert!(!task.is_active());
+        assert!(timer.waiting().is_empty());
+        assert_eq!(transport.requests().len(), 1);
+        assert_eq!(*statuses.borrow(), vec![StatusCode::INTERNAL_SERVER_ERROR]);
+    }
+
+    #[test]
+    fn dropping_the_task_cancels_the_retry() {
+        let (transport, timer) = (Rc::new(MemoryTransport::new()), Rc::new(ManualTimer::new()));
+        transport.respond(StatusCode::SERVICE_UNAVAILABLE, "");
+        let (callback, statuses) = statuses();
+        let task = send_with_retry(
+            transport.clone(),
+            timer.clone(),
+            get(),
+            3,
+            None,
+            false,
+            None,
+            callback,
+        );
+        drop(task);
+        assert!(!timer.fire());
+        assert_eq!(transport.requests().len(), 1);
+        assert!(statuses.borrow().is_empty());
+    }
+
+    fn backoff_max(attempt: u32) -> Duration {
+        Duration::from_millis(INITIAL_BACKOFF_MILLIS << (attempt - 1))
+    }
+}
@@ -1,10 +1,10 @@
 use crate::api::error::APIError;
+use crate::api::transport::RequestTask;
 use crate::api::users::UserResponseItem;
 use crate::api::{APIClient, FormUrlEncoded};
 use crate::loader_task::LoadingFunction;
 use serde::Deserialize;
 use std::collections::HashMap;
-use yew::services::fetch::FetchTask;
 use yew::Callback;
 
 #[derive(Deserialize, Debug, PartialEq, Clone)]
@@ -20,10 +20,11 @@ impl APIClient {
         password: String,
         loader: LoadingFunction,
         callback: Callback<Result<LoginResponse, APIError>>,
-    ) -> FetchTask {
+    ) -> RequestTask {
         let mut body = HashMap::new();
         body.insert("email", email);
         body.insert("password", password);
+        // Not retried, as each attempt counts towards the API's limit on failed logins
         self.post(
             "sessions/login",
             vec![],
@@ -37,7 +38,7 @@ impl APIClient {
         &self,
         loader: LoadingFunction,
         callback: Callback<Result<(), APIError>>,
-    ) -> FetchTask {
+    ) -> RequestTask {
         self.delete("sessions/logout", vec![], Some(loader), callback)
     }
 }
@@ -0,0 +1,82 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::Duration;
+use yew::services::{Task, TimeoutService};
+use yew::Callback;
+
+// Schedules retries and timeouts, dropping the task cancels the timer
+pub trait Timer {
+    fn spawn(&self, delay: Duration, callback: Callback<()>) -> Box<dyn Task>;
+}
+
+// Waits using the browser's setTimeout
+pub struct TimeoutTimer;
+
+impl Timer for TimeoutTimer {
+    fn spawn(&self, delay: Duration, callback: Callback<()>) -> Box<dyn Task> {
+        Box::new(TimeoutService::spawn(delay, callback))
+    }
+}
+
+// Keeps every timer waiting until it is fired by hand
+// This allows retries to be tested outside of a browser, without waiting for them
+#[derive(Default)]
+pub struct ManualTimer {
+    waiting: RefCell<Vec<Waiting>>,
+}
+
+struct Waiting {
+    delay: Duration,
+    callback: Callback<()>,
+    done: Rc<Cell<bool>>, // Fired or cancelled
+}
+
+impl ManualTimer {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    // The delays of the timers which haven't been fired or cancelled, oldest first
+    pub fn waiting(&self) -> Vec<Duration> {
+        self.waiting.borrow_mut().retain(|w| !w.done.get());
+        self.waiting.borrow().iter().map(|w| w.delay).collect()
+    }
+
+    // Fires the oldest timer which is still waiting, returning false if there isn't one
+    pub fn fire(&self) -> bool {
+        self.waiting.borrow_mut().retain(|w| !w.done.get());
+        if self.waiting.borrow().is_empty() {
+            return false;
+        }
+        let next = self.waiting.borrow_mut().remove(0);
+        next.done.set(true);
+        next.callback.emit(());
+        true
+    }
+}
+
+impl Timer for ManualTimer {
+    fn spawn(&self, delay: Duration, callback: Callback<()>) -> Box<dyn Task> {
+        let done = Rc::new(Cell::new(false));
+        self.waiting.borrow_mut().push(Waiting {
+            delay,
+            callback,
+            done: done.clone(),
+        });
+        Box::new(ManualTimerTask(done))
+    }
+}
+
+struct ManualTimerTask(Rc<Cell<bool>>);
+
+impl Task for ManualTimerTask {
+    fn is_active(&self) -> bool {
+        !self.0.get()
+    }
+}
+
+impl Drop for ManualTimerTask {
+    fn drop(&mut self) {
+        self.0.set(true);
+    }
+}
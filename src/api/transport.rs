@@ -0,0 +1,172 @@
+use crate::api::xhr;
+use http::{Request, Response, StatusCode};
+use std::cell::{Cell, Ref, RefCell};
+use std::collections::VecDeque;
+use std::rc::Rc;
+use yew::format::{Binary, Nothing};
+use yew::services::fetch::FetchService;
+use yew::services::Task;
+use yew::Callback;
+
+// Dropping the task cancels the request, the callback will then never be called
+pub type RequestTask = Box<dyn Task>;
+
+// How much of a request body has been sent so far
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UploadProgress {
+    pub sent: u64,
+    pub total: u64,
+}
+
+impl UploadProgress {
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.sent as f64 / self.total as f64
+        }
+    }
+}
+
+pub trait Transport {
+    fn send(&self, request: Request<Binary>, callback: Callback<Response<Binary>>) -> RequestTask;
+
+    // Transports which can't report progress just send the request
+    fn send_with_progress(
+        &self,
+        request: Request<Binary>,
+        _progress: Callback<UploadProgress>,
+        callback: Callback<Response<Binary>>,
+    ) -> RequestTask {
+        self.send(request, callback)
+    }
+}
+
+// Sends requests using the browser fetch API
+pub struct FetchTransport;
+
+impl Transport for FetchTransport {
+    fn send(&self, request: Request<Binary>, callback: Callback<Response<Binary>>) -> RequestTask {
+        Box::new(FetchService::fetch_binary(request, callback).unwrap())
+    }
+
+    fn send_with_progress(
+        &self,
+        request: Request<Binary>,
+        progress: Callback<UploadProgress>,
+        callback: Callback<Response<Binary>>,
+    ) -> RequestTask {
+        Box::new(xhr::send(request, progress, callback))
+    }
+}
+
+// Records every request, and replies immediately with the next scripted response
+// This allows the API client to be used outside of a browser
+#[derive(Default)]
+pub struct MemoryTransport {
+    requests: RefCell<Vec<Request<Vec<u8>>>>,
+    responses: RefCell<VecDeque<Response<Binary>>>,
+    hold_next: Cell<bool>,
+    held: RefCell<Vec<Held>>,
+}
+
+struct Held {
+    response: Response<Binary>,
+    callback: Callback<Response<Binary>>,
+    done: Rc<Cell<bool>>, // Delivered or cancelled
+}
+
+impl MemoryTransport {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn respond(&self, status: StatusCode, body: &str) {
+        let response = Response::builder()
+            .status(status)
+            .body(Ok(body.as_bytes().to_vec()))
+            .unwrap();
+        self.respond_with(response);
+    }
+
+    // For responses which need headers
+    pub fn respond_with(&self, response: Response<Binary>) {
+        self.responses.borrow_mut().push_back(response);
+    }
+
+    // The request will fail as if there was a network error
+    pub fn fail(&self) {
+        let response = Response::builder().body(Nothing.into()).unwrap();
+        self.responses.borrow_mut().push_back(response);
+    }
+
+    pub fn requests(&self) -> Ref<'_, Vec<Request<Vec<u8>>>> {
+        self.requests.borrow()
+    }
+
+    // The next request stays in flight until release is called, instead of being replied to
+    pub fn hold(&self) {
+        self.hold_next.set(true);
+    }
+
+    // Replies to the requests which were held, unless they have been cancelled
+    pub fn release(&self) {
+        let held = self.held.take();
+        for h in held.into_iter().filter(|h| !h.done.replace(true)) {
+            h.callback.emit(h.response);
+        }
+    }
+}
+
+impl Transport for MemoryTransport {
+    fn send(&self, request: Request<Binary>, callback: Callback<Response<Binary>>) -> RequestTask {
+        let (parts, body) = request.into_parts();
+        let body = body.unwrap_or_default();
+        self.requests
+            .borrow_mut()
+            .push(Request::from_parts(parts, body));
+        let response = self
+            .responses
+            .borrow_mut()
+            .pop_front()
+            .expect("no scripted response for the request");
+        if self.hold_next.replace(false) {
+            let done = Rc::new(Cell::new(false));
+            self.held.borrow_mut().push(Held {
+                response,
+                callback,
+                done: done.clone(),
+            });
+            return Box::new(HeldTask(done));
+        }
+        callback.emit(response);
+        Box::new(CompletedTask)
+    }
+}
+
+struct HeldTask(Rc<Cell<bool>>);
+
+impl Task for HeldTask {
+    fn is_active(&self) -> bool {
+        !self.0.get()
+    }
+}
+
+impl Drop for HeldTask {
+    fn drop(&mut self) {
+        self.0.set(true);
+    }
+}
+
+// Returned when the response has already been delivered
+pub struct CompletedTask;
+
+impl Task for CompletedTask {
+    fn is_active(&self) -> bool {
+        false
+    }
+}
+
+impl Drop for CompletedTask {
+    fn drop(&mut self) {}
+}
@@ -0,0 +1,278 @@
+use crate::api::error::APIError;
+use crate::api::transport::RequestTask;
+use crate::api::{APIClient, Empty, FormUrlEncoded, RequestBody, RequestOptions};
+use crate::loader_task::{BoxedLoadingTask, LoadingFunction, LoadingTaskConfig};
+use headers::{ContentRange, ContentType};
+use http::Method;
+use serde::Deserialize;
+use std::cell::{Cell, RefCell};
+use std::cmp::min;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+use std::time::Duration;
+use yew::format::Binary;
+use yew::services::Task;
+use yew::Callback;
+
+// Files larger than this are uploaded in chunks of this size
+pub const CHUNK_SIZE: usize = 1024 * 1024;
+
+// Allows for a slow connection, while still noticing a stalled chunk reasonably quickly
+const CHUNK_TIMEOUT: Duration = Duration::from_secs(2 * 60);
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct UploadSessionResponse {
+    pub id: String,
+    // How many bytes the API has stored, the next chunk starts here
+    pub received: u64,
+}
+
+// Upload sessions which haven't been finalized yet, by file
+// Uploading the same file again continues from where the previous attempt stopped
+// These are only kept in memory, so an upload can't be resumed after the page is reloaded
+#[derive(Default)]
+pub struct UploadSessions(RefCell<HashMap<String, String>>);
+
+impl UploadSessions {
+    fn key(filename: &str, data: &[u8]) -> String {
+        let mut hasher = DefaultHasher::new();
+        data.hash(&mut hasher);
+        format!("{}:{}:{:x}", filename, data.len(), hasher.finish())
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        self.0.borrow().get(key).cloned()
+    }
+
+    fn insert(&self, key: String, id: String) {
+        self.0.borrow_mut().insert(key, id);
+    }
+
+    fn remove(&self, key: &str) {
+        self.0.borrow_mut().remove(key);
+    }
+}
+
+pub struct Chunk(Vec<u8>);
+
+impl RequestBody for Chunk {
+    fn content_type(&self) -> Option<ContentType> {
+        Some(ContentType::octet_stream())
+    }
+}
+
+impl From<Chunk> for Binary {
+    fn from(chunk: Chunk) -> Self {
+        Ok(chunk.0)
+    }
+}
+
+// Uploads a file in chunks to `{path}`, then finalizes it with the given form
+// 1. POST {path} creates an upload session, or GET {path}/{id} finds how much of an earlier
+//    session was received
+// 2. PUT {path}/{id} sends each chunk, with a Content-Range header
+// 3. POST {path}/{id}/finalize sends the form, which the API uses to create the resource
+// Each chunk is retried after a temporary failure, and if the upload still fails the session is
+// kept, so that uploading the same file again resumes it
+pub fn upload_in_chunks(
+    client: &APIClient,
+    path: &str,
+    filename: String,
+    data: Vec<u8>,
+    finalize: Vec<(&'static str, String)>,
+    loader: LoadingFunction,
+    callback: Callback<Result<(), APIError>>,
+) -> RequestTask {
+    let key = UploadSessions::key(&filename, &data);
+    let upload = Rc::new(ChunkedUpload {
+        client: client.clone(),
+        path: path.to_string(),
+        key,
+        filename,
+        data,
+        finalize,
+        step: Cell::new(0),
+        finished: Cell::new(false),
+        loader_task: RefCell::new(Some(loader(LoadingTaskConfig::default()))),
+        callback,
+        current: RefCell::new(None),
+    });
+    if let Some(l) = upload.loader_task.borrow().as_ref() {
+        let weak = Rc::downgrade(&upload);
+        l.set_on_cancel(Some(Callback::from(move |_| {
+            if let Some(u) = weak.upgrade() {
+                u.finish(Err(APIError::Cancelled));
+            }
+        })));
+    }
+    upload.start();
+    Box::new(ChunkedUploadTask(upload))
+}
+
+struct ChunkedUpload {
+    client: APIClient,
+    path: String,
+    key: String,
+    filename: String,
+    data: Vec<u8>,
+    finalize: Vec<(&'static str, String)>,
+    // Incremented for every request, so a response that arrives synchronously isn't replaced
+    step: Cell<u32>,
+    finished: Cell<bool>,
+    loader_task: RefCell<Option<BoxedLoadingTask>>,
+    callback: Callback<Result<(), APIError>>,
+    current: RefCell<Option<RequestTask>>,
+}
+
+impl ChunkedUpload {
+    fn start(self: &Rc<Self>) {
+        match self.client.uploads.get(&self.key) {
+            Some(id) => {
+                self.set_text("Resuming upload…");
+                let options = RequestOptions::default().cache(false);
+                let path = format!("{}/{}", self.path, id);
+                let callback = self.callback(Self::on_resume);
+                self.run(|c| {
+                    c.request_with_options(&path, vec![], Method::GET, Empty, options, callback)
+                });
+            }
+            None => self.create_session(),
+        }
+    }
+
+    fn create_session(self: &Rc<Self>) {
+        let form = FormUrlEncoded(vec![
+            ("filename", self.filename.clone()),
+            ("size", self.data.len().to_string()),
+        ]);
+        // Safe to retry, an extra session is never used and expires on the API
+        let options = RequestOptions::default().retry_non_idempotent(true);
+        let callback = self.callback(Self::on_session);
+        let path = self.path.clone();
+        self.run(|c| c.request_with_options(&path, vec![], Method::POST, form, options, callback));
+    }
+
+    fn on_resume(self: &Rc<Self>, r: Result<UploadSessionResponse, APIError>) {
+        match r {
+            // The session has expired, so the upload starts again
+            Err(APIError::NotFound) => {
+                self.client.uploads.remove(&self.key);
+                self.create_session();
+            }
+            r => self.on_session(r),
+        }
+    }
+
+    fn on_session(self: &Rc<Self>, r: Result<UploadSessionResponse, APIError>) {
+        match r {
+            Ok(s) => {
+                self.client.uploads.insert(self.key.clone(), s.id.clone());
+                self.send_chunk(s);
+            }
+            Err(e) => self.finish(Err(e)),
+        }
+    }
+
+    fn send_chunk(self: &Rc<Self>, session: UploadSessionResponse) {
+        let total = self.data.len();
+        let start = min(session.received as usize, total);
+        if let Some(l) = self.loader_task.borrow().as_ref() {
+            let fraction = start as f64 / total as f64;
+            l.set_progress(Some(fraction));
+            l.set_text(Some(format!("Uploading… {}%", (fraction * 100.0) as u32)));
+        }
+        let path = format!("{}/{}", self.path, session.id);
+        if start == total {
+            // Safe to retry, the API creates the item only once per session
+            let form = FormUrlEncoded(self.finalize.clone());
+            let options = RequestOptions::default().retry_non_idempotent(true);
+            let callback = self.callback(Self::on_finalized);
+            let path = format!("{}/finalize", path);
+            self.run(|c| {
+                c.request_with_options(&path, vec![], Method::POST, form, options, callback)
+            });
+            return;
+        }
+        let end = min(start + CHUNK_SIZE, total);
+        let range = ContentRange::bytes(start as u64..end as u64, total as u64).unwrap();
+        let options = RequestOptions::default()
+            .timeout(Some(CHUNK_TIMEOUT))
+            .content_range(range);
+        let chunk = Chunk(self.data[start..end].to_vec());
+        let callback = self.callback(Self::on_chunk);
+        self.run(|c| c.request_with_options(&path, vec![], Method::PUT, chunk, options, callback));
+    }
+
+    fn on_chunk(self: &Rc<Self>, r: Result<UploadSessionResponse, APIError>) {
+        match r {
+            Ok(s) => self.send_chunk(s),
+            Err(e) => self.finish(Err(e)),
+        }
+    }
+
+    fn on_finalized(self: &Rc<Self>, r: Result<(), APIError>) {
+        if r.is_ok() {
+            self.client.uploads.remove(&self.key);
+        }
+        self.finish(r);
+    }
+
+    fn set_text(&self, text: &str) {
+        if let Some(l) = self.loader_task.borrow().as_ref() {
+            l.set_text(Some(text.to_string()));
+        }
+    }
+
+    fn callback<T: 'static>(
+        self: &Rc<Self>,
+        f: fn(&Rc<Self>, Result<T, APIError>),
+    ) -> Callback<Result<T, APIError>> {
+        let weak = Rc::downgrade(self);
+        Callback::once(move |r| {
+            if let Some(u) = weak.upgrade() {
+                f(&u, r);
+            }
+        })
+    }
+
+    fn run(self: &Rc<Self>, send: impl FnOnce(&APIClient) -> RequestTask) {
+        let step = self.step.get() + 1;
+        self.step.set(step);
+        let task = send(&self.client);
+        // The response may already have arrived, and the next request been sent
+        if !self.finished.get() && self.step.get() == step {
+            *self.current.borrow_mut() = Some(task);
+        }
+    }
+
+    fn finish(&self, result: Result<(), APIError>) {
+        if self.finished.replace(true) {
+            return;
+        }
+        self.cancel();
+        let loader_task = self.loader_task.borrow_mut().take();
+        drop(loader_task);
+        self.callback.emit(result);
+    }
+
+    fn cancel(&self) {
+        let current = self.current.borrow_mut().take();
+        drop(current);
+    }
+}
+
+struct ChunkedUploadTask(Rc<ChunkedUpload>);
+
+impl Task for ChunkedUploadTask {
+    fn is_active(&self) -> bool {
+        !self.0.finished.get()
+    }
+}
+
+impl Drop for ChunkedUploadTask {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
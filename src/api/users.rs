@@ -1,9 +1,10 @@
 use crate::api::error::APIError;
-use crate::api::{APIClient, Counted, FormUrlEncoded};
+use crate::api::transport::RequestTask;
+use crate::api::{APIClient, Counted, Empty, FormUrlEncoded, RequestOptions};
 use crate::loader_task::LoadingFunction;
+use http::Method;
 use serde::Deserialize;
 use std::collections::HashMap;
-use yew::services::fetch::FetchTask;
 use yew::Callback;
 
 #[derive(Deserialize, Debug, Clone, PartialEq)]
@@ -11,17 +12,22 @@ pub struct UserResponseItem {
     pub id: u32,
     pub name: String,
     pub email: String,
+    // Incremented by the API on every update, if it supports it
+    #[serde(default)]
+    pub version: Option<u32>,
 }
 
 impl APIClient {
+    // on_refresh is called if the page given from the cache has changed since
     pub fn users_list(
         &self,
         limit: u32,
         offset: u32,
         search: Option<String>,
         loader: LoadingFunction,
+        on_refresh: Callback<()>,
         callback: Callback<Result<Counted<UserResponseItem>, APIError>>,
-    ) -> FetchTask {
+    ) -> RequestTask {
         let mut query = Vec::new();
         query.push(("limit".to_string(), limit.to_string()));
         query.push(("offset".to_string(), offset.to_string()));
@@ -31,7 +37,16 @@ impl APIClient {
                 query.push(("search".to_string(), s));
             }
         }
-        self.get("users", query, Some(loader), callback)
+        self.request_with_options(
+            "users",
+            query,
+            Method::GET,
+            Empty,
+            RequestOptions::default()
+                .loader(Some(loader))
+                .on_refresh(on_refresh),
+            callback,
+        )
     }
 
     pub fn users_create(
@@ -40,7 +55,7 @@ impl APIClient {
         email: String,
         loader: LoadingFunction,
         callback: Callback<Result<UserResponseItem, APIError>>,
-    ) -> FetchTask {
+    ) -> RequestTask {
         let mut body = HashMap::new();
         body.insert("name", name);
         body.insert("email", email);
@@ -58,7 +73,7 @@ impl APIClient {
         id: u32,
         loader: Option<LoadingFunction>,
         callback: Callback<Result<UserResponseItem, APIError>>,
-    ) -> FetchTask {
+    ) -> RequestTask {
         self.get(&format!("users/{}", id), vec![], loader, callback)
     }
 
@@ -67,17 +82,22 @@ impl APIClient {
         id: u32,
         name: String,
         email: String,
+        version: Option<u32>,
         loader: LoadingFunction,
         callback: Callback<Result<UserResponseItem, APIError>>,
-    ) -> FetchTask {
+    ) -> RequestTask {
         let mut body = HashMap::new();
         body.insert("name", name);
         body.insert("email", email);
-        self.put(
+        self.request_with_options(
             &format!("users/{}", id),
             vec![],
+            Method::PUT,
             FormUrlEncoded(body),
-            Some(loader),
+            RequestOptions::default()
+                .if_match(version)
+                .queue_offline(format!("Changes to user {}", id))
+                .loader(Some(loader)),
             callback,
         )
     }
@@ -87,7 +107,7 @@ impl APIClient {
         id: u32,
         loader: LoadingFunction,
         callback: Callback<Result<(), APIError>>,
-    ) -> FetchTask {
+    ) -> RequestTask {
         self.delete(&format!("users/{}", id), vec![], Some(loader), callback)
     }
 }
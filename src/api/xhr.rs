@@ -0,0 +1,130 @@
+use crate::api::transport::UploadProgress;
+use http::{Request, Response};
+use std::cell::Cell;
+use std::rc::Rc;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{ProgressEvent, XmlHttpRequest, XmlHttpRequestResponseType};
+use yew::format::{Binary, Nothing};
+use yew::services::Task;
+use yew::Callback;
+
+// Fetch can't report how much of the body has been sent, but XMLHttpRequest can
+pub fn send(
+    request: Request<Binary>,
+    progress: Callback<UploadProgress>,
+    callback: Callback<Response<Binary>>,
+) -> XhrTask {
+    let xhr = XmlHttpRequest::new().unwrap();
+    let done = Rc::new(Cell::new(false));
+    let (parts, body) = request.into_parts();
+    xhr.open_with_async(parts.method.as_str(), &parts.uri.to_string(), true)
+        .unwrap();
+    xhr.set_response_type(XmlHttpRequestResponseType::Arraybuffer);
+    for (name, value) in parts.headers.iter() {
+        if let Ok(v) = value.to_str() {
+            xhr.set_request_header(name.as_str(), v).unwrap();
+        }
+    }
+
+    let on_progress = {
+        let done = done.clone();
+        let f: Box<dyn FnMut(ProgressEvent)> = Box::new(move |e: ProgressEvent| {
+            if !done.get() && e.length_computable() {
+                progress.emit(UploadProgress {
+                    sent: e.loaded() as u64,
+                    total: e.total() as u64,
+                });
+            }
+        });
+        Closure::wrap(f)
+    };
+    let on_load = {
+        let (done, xhr, callback) = (done.clone(), xhr.clone(), callback.clone());
+        let f: Box<dyn FnMut()> = Box::new(move || {
+            if !done.replace(true) {
+                callback.emit(response(&xhr));
+            }
+        });
+        Closure::wrap(f)
+    };
+    // Matches fetch, which gives a response with an error body when the request fails
+    let on_error = {
+        let (done, callback) = (done.clone(), callback.clone());
+        let f: Box<dyn FnMut()> = Box::new(move || {
+            if !done.replace(true) {
+                callback.emit(Response::new(Nothing.into()));
+            }
+        });
+        Closure::wrap(f)
+    };
+    xhr.upload()
+        .unwrap()
+        .set_onprogress(Some(on_progress.as_ref().unchecked_ref()));
+    xhr.set_onload(Some(on_load.as_ref().unchecked_ref()));
+    xhr.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+
+    let sent = match &body {
+        Ok(b) => xhr.send_with_opt_u8_array(Some(b)),
+        Err(_) => xhr.send(),
+    };
+    if sent.is_err() && !done.replace(true) {
+        callback.emit(Response::new(Nothing.into()));
+    }
+    XhrTask {
+        xhr,
+        done,
+        _closures: (on_progress, on_load, on_error),
+    }
+}
+
+fn response(xhr: &XmlHttpRequest) -> Response<Binary> {
+    let mut builder = Response::builder().status(xhr.status().unwrap_or(0));
+    let headers = xhr.get_all_response_headers().unwrap_or_default();
+    for line in headers.split("\r\n") {
+        let mut split = line.splitn(2, ':');
+        if let (Some(name), Some(value)) = (split.next(), split.next()) {
+            builder = builder.header(name.trim(), value.trim());
+        }
+    }
+    let body = match xhr.response() {
+        Ok(b) => Ok(js_sys::Uint8Array::new(&b).to_vec()),
+        Err(_) => Nothing.into(),
+    };
+    builder
+        .body(body)
+        .unwrap_or_else(|_| Response::new(Nothing.into()))
+}
+
+// The progress, load and error handlers
+type Handlers = (
+    Closure<dyn FnMut(ProgressEvent)>,
+    Closure<dyn FnMut()>,
+    Closure<dyn FnMut()>,
+);
+
+pub struct XhrTask {
+    xhr: XmlHttpRequest,
+    done: Rc<Cell<bool>>,
+    _closures: Handlers,
+}
+
+impl Task for XhrTask {
+    fn is_active(&self) -> bool {
+        !self.done.get()
+    }
+}
+
+impl Drop for XhrTask {
+    fn drop(&mut self) {
+        if !self.done.replace(true) {
+            self.xhr.abort().unwrap();
+        }
+        // The closures are about to be freed, so the request mustn't call them
+        if let Ok(upload) = self.xhr.upload() {
+            upload.set_onprogress(None);
+        }
+        self.xhr.set_onload(None);
+        self.xhr.set_onerror(None);
+    }
+}
@@ -1,13 +1,24 @@
 use crate::api::error::APIError;
+use crate::api::outbox::{self, OnlineWatcher, OutboxEntry, OutboxWatcher};
 use crate::api::session::LoginResponse;
+use crate::api::transport::RequestTask;
 use crate::api::users::UserResponseItem;
 use crate::api::APIClient;
-use crate::auth::PersistedAuth;
+use crate::auth::{AuthWatcher, PersistedAuth};
+use crate::components::error::ErrorAlert;
 use crate::components::footer::FooterComponent;
 use crate::components::header::HeaderComponent;
+use crate::components::idle_warning::IdleWarningComponent;
 use crate::components::loading::{LoadingComponent, LoadingProps};
+use crate::components::loading_bar::LoadingBarComponent;
+use crate::components::outbox_alert::{OutboxAlertComponent, OutboxFailure};
 use crate::components::sidebar::{SidebarActive, SidebarComponent};
-use crate::loader_task::{LoadingFunction, LoadingTask, LoadingTaskConfig};
+use crate::config::{Config, ConfigError};
+use crate::crash;
+use crate::idle::ActivityTracker;
+use crate::loader_task::{
+    LoadingFunction, LoadingTask, LoadingTaskConfig, LoadingTaskId, LoadingTasks,
+};
 use crate::routes::dashboard::DashboardRoute;
 use crate::routes::forgot_password::ForgotPasswordRoute;
 use crate::routes::gallery_create::CreateGalleryItemRoute;
@@ -19,35 +30,69 @@ use crate::routes::password_reset::PasswordResetRoute;
 use crate::routes::users_create::CreateUserRoute;
 use crate::routes::users_edit::EditUserRoute;
 use crate::routes::users_list::ListUsersRoute;
-use crate::routes::{on_route_change, AppRoute, Route, RouteAgentBridge, RouteService, Router};
+use crate::routes::{
+    login_redirect, on_route_change, AppRoute, Route, RouteAgentBridge, RouteService, Router,
+};
+use std::cell::Cell;
 use std::rc::Rc;
+use std::time::Duration;
 use yew::prelude::*;
 use yew::services::fetch::FetchTask;
+use yew::services::interval::{IntervalService, IntervalTask};
 use yew_router::agent::RouteRequest;
 
-#[cfg(debug_assertions)]
-const API_URL: &str = "http://localhost:8001/api/";
-#[cfg(not(debug_assertions))]
-const API_URL: &str = "https://api.kiwijoinerydevon.co.uk";
-
 pub struct App {
     link: ComponentLink<Self>,
-    api_client: APIClient,
-    loading: LoadingProps,
+    config_task: Option<FetchTask>,
+    config_error: Option<ConfigError>,
+    api_client: Option<APIClient>,
+    loading: LoadingTasks,
+    next_loading_id: Rc<Cell<LoadingTaskId>>,
     current_user: Option<UserResponseItem>,
-    current_user_task: Option<FetchTask>,
+    current_user_task: Option<RequestTask>,
     router_agent: RouteAgentBridge,
     route_service: RouteService,
+    _auth_watcher: AuthWatcher,
+    _expiry_interval: IntervalTask,
+    activity: ActivityTracker,
+    idle_logout_after: Option<Duration>,
+    idle_interval: Option<IntervalTask>,
+    idle_warning: Option<u32>,
+    idle_logout_task: Option<RequestTask>,
+    pending_changes: usize,
+    outbox_task: Option<RequestTask>,
+    outbox_failures: Vec<OutboxFailure>,
+    _online_watcher: OnlineWatcher,
+    _outbox_watcher: OutboxWatcher,
 }
 
+// How long the user has to respond before being logged out
+const IDLE_WARNING_SECS: u64 = 60;
+
+// How often to check whether a remembered session has expired, while the app is left open
+const EXPIRY_CHECK_SECS: u64 = 60;
+
 pub enum Msg {
-    LoggedIn(LoginResponse),
+    ConfigResponse(Result<Config, ConfigError>),
+    LoggedIn(LoginResponse, bool),
     Logout,
+    SessionExpired,
+    CheckExpiry,
+    AuthChangedElsewhere,
     UserResponse(Result<UserResponseItem, APIError>),
     RouteUpdated(Route),
-    StartLoading(LoadingTaskConfig),
-    StopLoading,
-    UpdateLoadingText(Option<String>),
+    StartLoading(LoadingTaskId, LoadingTaskConfig),
+    StopLoading(LoadingTaskId),
+    UpdateLoadingText(LoadingTaskId, Option<String>),
+    UpdateLoadingCancel(LoadingTaskId, Option<Callback<()>>),
+    UpdateLoadingProgress(LoadingTaskId, Option<f64>),
+    IdleTick,
+    StayActive,
+    IdleLogoutResult(Result<(), APIError>),
+    OutboxChanged,
+    ReplayOutbox,
+    OutboxReplayed(OutboxEntry, Result<(), APIError>),
+    DismissOutboxFailures,
 }
 
 impl Component for App {
@@ -55,32 +100,95 @@ impl Component for App {
     type Properties = ();
 
     fn create(_: Self::Properties, link: ComponentLink<Self>) -> Self {
-        let mut client = APIClient::new(API_URL);
-        let auth = PersistedAuth::load();
+        let config_task = Config::load(link.callback(Msg::ConfigResponse));
         let route_service = RouteService::new();
-        on_route_change(route_service.get_route(), auth.is_some());
-        match auth {
-            None => {}
-            Some(a) => client.set_auth_header(a.into()),
-        }
+        on_route_change(route_service.get_route(), PersistedAuth::load().is_some());
         let router_agent = RouteAgentBridge::new(link.callback(Msg::RouteUpdated));
+        let auth_watcher = PersistedAuth::watch(link.callback(|_| Msg::AuthChangedElsewhere));
+        let expiry_interval = IntervalService::spawn(
+            Duration::from_secs(EXPIRY_CHECK_SECS),
+            link.callback(|_| Msg::CheckExpiry),
+        );
+        let online_watcher = OnlineWatcher::new(link.callback(|_| Msg::ReplayOutbox));
+        let outbox_watcher = OutboxWatcher::new(link.callback(|_| Msg::OutboxChanged));
         Self {
             link,
-            api_client: client,
-            loading: LoadingProps::default(),
+            config_task: Some(config_task),
+            config_error: None,
+            api_client: None,
+            loading: LoadingTasks::default(),
+            next_loading_id: Rc::new(Cell::new(0)),
             current_user: None,
             current_user_task: None,
             router_agent,
             route_service,
+            _auth_watcher: auth_watcher,
+            _expiry_interval: expiry_interval,
+            activity: ActivityTracker::new(),
+            idle_logout_after: None,
+            idle_interval: None,
+            idle_warning: None,
+            idle_logout_task: None,
+            pending_changes: 0,
+            outbox_task: None,
+            outbox_failures: Vec::new(),
+            _online_watcher: online_watcher,
+            _outbox_watcher: outbox_watcher,
         }
     }
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match msg {
-            Msg::LoggedIn(r) => {
-                let auth = PersistedAuth::persist(r.user.id, r.token);
-                self.api_client.set_auth_header(auth.into());
+            Msg::ConfigResponse(r) => {
+                self.config_task = None;
+                match r {
+                    Ok(config) => {
+                        crash::set_report_url(config.error_report_url.clone());
+                        let mut client = APIClient::new(config.api_url.as_str());
+                        client.set_max_retries(config.max_retries);
+                        client.set_chunked_uploads(config.chunked_uploads);
+                        client.set_auth_scheme(config.auth_scheme);
+                        client.set_on_unauthorized(self.link.callback(|_| Msg::SessionExpired));
+                        client.set_on_outbox_change(self.link.callback(|_| Msg::OutboxChanged));
+                        let auth = PersistedAuth::load();
+                        match &auth {
+                            None => {}
+                            Some(a) => {
+                                client.set_auth_header(a.user_id, a.auth_header(config.auth_scheme))
+                            }
+                        }
+                        self.api_client = Some(client);
+                        self.update_pending_changes();
+                        if config.idle_logout_mins > 0 {
+                            let mins = u64::from(config.idle_logout_mins);
+                            self.idle_logout_after = Some(Duration::from_secs(mins * 60));
+                            self.idle_interval = Some(IntervalService::spawn(
+                                Duration::from_secs(1),
+                                self.link.callback(|_| Msg::IdleTick),
+                            ));
+                        }
+                        match auth {
+                            None => {}
+                            Some(a) => {
+                                self.current_user_task = Some(self.load_user_task(a.user_id));
+                                self.link.send_message(Msg::ReplayOutbox);
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        log::error!("Couldn't load the configuration: {}", e);
+                        self.config_error = Some(e);
+                    }
+                }
+            }
+            Msg::LoggedIn(r, remember) => {
+                let auth = PersistedAuth::persist(r.user.id, r.token, remember);
+                self.activity.record();
+                let header = auth.auth_header(self.api_client_mut().auth_scheme());
+                self.api_client_mut().set_auth_header(r.user.id, header);
+                self.update_pending_changes();
                 self.current_user_task = Some(self.load_user_task(r.user.id));
+                self.link.send_message(Msg::ReplayOutbox);
                 let dest = match self.route_service.get_route().state.redirect_on_login {
                     None => Route::from(AppRoute::Dashboard),
                     Some(s) => Route::new_default_state(s),
@@ -88,35 +196,129 @@ impl Component for App {
                 self.router_agent.send(RouteRequest::ChangeRoute(dest));
             }
             Msg::Logout => {
-                self.current_user_task = None;
-                self.current_user = None;
                 PersistedAuth::remove();
-                self.api_client.remove_auth_header();
+                self.clear_session();
                 self.router_agent
                     .send(RouteRequest::ChangeRoute(Route::from(AppRoute::Login)));
             }
+            Msg::SessionExpired => {
+                // Several requests may be rejected at once, only the first needs handling
+                if !self.is_signed_in() {
+                    return false;
+                }
+                log::info!("Session is no longer valid, signing out");
+                PersistedAuth::remove();
+                self.clear_session();
+                let current = self.route_service.get_route();
+                self.router_agent
+                    .send(RouteRequest::ChangeRoute(login_redirect(current.route)));
+            }
+            // Loading the session removes it once it has expired
+            Msg::CheckExpiry => {
+                if self.is_signed_in() && PersistedAuth::load().is_none() {
+                    self.link.send_message(Msg::SessionExpired);
+                }
+                return false;
+            }
+            // This tab's storage has already been updated
+            Msg::AuthChangedElsewhere => {
+                if self.api_client.is_none() {
+                    return false; // The auth will be loaded along with the config
+                }
+                match PersistedAuth::load() {
+                    None => {
+                        if self.is_signed_in() {
+                            log::info!("Signed out in another tab");
+                            self.clear_session();
+                        }
+                    }
+                    Some(a) => {
+                        let client = self.api_client.as_ref().unwrap();
+                        let header = a.auth_header(client.auth_scheme());
+                        let current = client.auth_header();
+                        if current.as_ref() != Some(&header) {
+                            log::info!("Signed in with another tab");
+                            self.activity.record();
+                            self.api_client_mut().set_auth_header(a.user_id, header);
+                            self.update_pending_changes();
+                            self.current_user = None;
+                            self.current_user_task = Some(self.load_user_task(a.user_id));
+                            self.link.send_message(Msg::ReplayOutbox);
+                        }
+                    }
+                }
+                on_route_change(self.route_service.get_route(), self.is_signed_in());
+            }
             Msg::UserResponse(res) => match res {
                 Ok(u) => self.current_user = Some(u),
                 Err(e) => log::error!("Couldn't load the current user: {}", e),
             },
             Msg::RouteUpdated(r) => {
-                on_route_change(r, self.api_client.auth_header().is_some());
+                self.activity.record();
+                on_route_change(r, self.is_signed_in());
+            }
+            Msg::StartLoading(id, cfg) => {
+                log::info!("Starting loading task {}", id);
+                self.loading.start(id, cfg);
             }
-            Msg::StartLoading(cfg) => {
-                log::info!("Starting loading");
-                if self.loading.active {
-                    log::error!("Global loader is already active");
+            Msg::StopLoading(id) => {
+                log::info!("Stopping loading task {}", id);
+                self.loading.finish(id);
+            }
+            // A task may send an update after it has been dropped, which is ignored
+            Msg::UpdateLoadingText(id, x) => match self.loading.get_mut(id) {
+                Some(t) => t.text = x,
+                None => return false,
+            },
+            Msg::UpdateLoadingCancel(id, x) => match self.loading.get_mut(id) {
+                Some(t) => t.on_cancel = x,
+                None => return false,
+            },
+            Msg::UpdateLoadingProgress(id, x) => match self.loading.get_mut(id) {
+                Some(t) => t.progress = x,
+                None => return false,
+            },
+            Msg::IdleTick => return self.check_idle(),
+            Msg::StayActive => {
+                self.activity.record();
+                self.idle_warning = None;
+            }
+            Msg::IdleLogoutResult(r) => {
+                // Signed out locally even if the server couldn't be reached
+                if let Err(e) = r {
+                    log::error!("Couldn't end the session: {}", e);
                 }
-                self.loading.active = true;
-                self.loading.delay_full_appearance = cfg.get_delay_full_appearance();
-                self.loading.text = None;
+                log::info!("Logged out due to inactivity");
+                self.link.send_message(Msg::Logout);
+                return false;
             }
-            Msg::StopLoading => {
-                log::info!("Stopping loading");
-                self.loading.active = false;
+            Msg::OutboxChanged => self.update_pending_changes(),
+            Msg::ReplayOutbox => return self.replay_outbox(),
+            Msg::OutboxReplayed(entry, r) => {
+                self.outbox_task = None;
+                match r {
+                    Ok(_) => {}
+                    // Still offline, or signed out, so the rest are kept until next time
+                    Err(APIError::RequestError)
+                    | Err(APIError::Timeout)
+                    | Err(APIError::Unauthorized(_)) => {
+                        outbox::release(entry.id);
+                        return false;
+                    }
+                    Err(e) => {
+                        log::warn!("Couldn't send {} from the outbox: {}", entry.description, e);
+                        self.outbox_failures.push(OutboxFailure {
+                            description: entry.description.clone(),
+                            error: e,
+                        });
+                    }
+                }
+                outbox::remove(entry.id);
+                self.update_pending_changes();
+                self.link.send_message(Msg::ReplayOutbox);
             }
-            Msg::UpdateLoadingText(x) => {
-                self.loading.text = x;
+            Msg::DismissOutboxFailures => {
+                self.outbox_failures.clear();
             }
         }
         true
@@ -127,16 +329,19 @@ impl Component for App {
     }
 
     fn view(&self) -> Html {
-        let loading_props = self.loading.clone();
-        let api_client = self.api_client.clone();
-        let link_clone = self.link.clone();
-        let loading_function = LoadingFunction(Rc::new(move |cfg| {
-            link_clone.send_message(Msg::StartLoading(cfg));
-            Box::new(AppLoadingTask {
-                link: link_clone.clone(),
-            })
-        }));
-        let on_login = self.link.callback(|x| Msg::LoggedIn(x));
+        let api_client = match &self.api_client {
+            Some(c) => c.clone(),
+            None => return self.boot_view(),
+        };
+        let loading_props = LoadingProps {
+            active: self.loading.has_foreground(),
+            delay_full_appearance: self.loading.delay_full_appearance(),
+            tasks: self.loading.foreground().cloned().collect(),
+        };
+        let loading_function = self.loading_function();
+        let on_login = self
+            .link
+            .callback(|(x, remember)| Msg::LoggedIn(x, remember));
         html! {
             <>
                 <HeaderComponent
@@ -145,8 +350,15 @@ impl Component for App {
                     current_user=self.current_user.clone()
                     on_logout=self.link.callback(|_| Msg::Logout)
                     api_client=api_client.clone()
+                    pending_changes=self.pending_changes
+                />
+                <OutboxAlertComponent
+                    failures=self.outbox_failures.clone()
+                    on_dismiss=self.link.callback(|_| Msg::DismissOutboxFailures)
                 />
                 <LoadingComponent with loading_props/>
+                <LoadingBarComponent active=self.loading.has_background() />
+                { self.idle_warning_view() }
                 <Router
                     render = Router::render(move |switch: AppRoute| {
                         match switch {
@@ -230,39 +442,173 @@ impl Component for App {
             </>
         }
     }
-
-    fn rendered(&mut self, first_render: bool) {
-        if first_render {
-            match self.api_client.auth_header() {
-                None => {}
-                Some(a) => {
-                    self.current_user_task =
-                        Some(self.load_user_task(a.0.username().parse().unwrap()))
-                }
-            }
-        }
-    }
 }
 
 impl App {
-    fn load_user_task(&self, id: u32) -> FetchTask {
+    fn load_user_task(&self, id: u32) -> RequestTask {
         self.api_client
+            .as_ref()
+            .expect("the API client is created once the configuration has loaded")
             .users_get(id, None, self.link.callback(Msg::UserResponse))
     }
+
+    // The stored session is left as it is, so callers which end it should remove it first
+    fn clear_session(&mut self) {
+        self.idle_warning = None;
+        self.idle_logout_task = None;
+        self.current_user_task = None;
+        self.current_user = None;
+        self.api_client_mut().remove_auth_header();
+        // The user's unsent changes are kept, and sent once they sign in again
+        self.update_pending_changes();
+    }
+
+    fn loading_function(&self) -> LoadingFunction {
+        let link = self.link.clone();
+        let next_id = self.next_loading_id.clone();
+        LoadingFunction(Rc::new(move |cfg| {
+            let id = next_id.get();
+            next_id.set(id.wrapping_add(1));
+            link.send_message(Msg::StartLoading(id, cfg));
+            Box::new(AppLoadingTask {
+                id,
+                link: link.clone(),
+            })
+        }))
+    }
+
+    // Warns the user once they have been idle for the configured time, then logs them out if
+    // they don't respond
+    fn check_idle(&mut self) -> ShouldRender {
+        let idle_logout_after = match self.idle_logout_after {
+            Some(d) => d.as_millis() as f64,
+            None => return false,
+        };
+        if !self.is_signed_in() || self.idle_logout_task.is_some() {
+            return false;
+        }
+        let idle = self.activity.idle_millis();
+        let remaining = idle_logout_after + (IDLE_WARNING_SECS * 1000) as f64 - idle;
+        let warning = if idle < idle_logout_after {
+            None
+        } else if remaining <= 0.0 {
+            self.idle_logout_task = Some(self.api_client.as_ref().unwrap().session_logout(
+                self.loading_function(),
+                self.link.callback(Msg::IdleLogoutResult),
+            ));
+            None
+        } else {
+            Some((remaining / 1000.0).ceil() as u32)
+        };
+        if self.idle_warning != warning {
+            self.idle_warning = warning;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn idle_warning_view(&self) -> Html {
+        match self.idle_warning {
+            None => html! {},
+            Some(s) => html! {
+                <IdleWarningComponent
+                    seconds_remaining=s
+                    on_stay=self.link.callback(|_| Msg::StayActive)
+                />
+            },
+        }
+    }
+
+    // Sends the changes which were made offline, one at a time in the order they were made
+    fn replay_outbox(&mut self) -> ShouldRender {
+        if self.outbox_task.is_some() || !self.is_signed_in() {
+            return false;
+        }
+        let user_id = match self.api_client.as_ref().and_then(|c| c.user_id()) {
+            Some(u) => u,
+            None => return false,
+        };
+        let entry = match outbox::claim_next(user_id) {
+            Some(e) => e,
+            None => return false,
+        };
+        log::info!("Sending {} from the outbox", entry.description);
+        let callback = {
+            let entry = entry.clone();
+            self.link
+                .callback_once(move |r| Msg::OutboxReplayed(entry, r))
+        };
+        self.outbox_task = Some(self.api_client.as_ref().unwrap().outbox_replay(
+            &entry,
+            Some(self.loading_function().background()),
+            callback,
+        ));
+        false
+    }
+
+    // Only the signed in user's changes are shown, and sent
+    fn update_pending_changes(&mut self) {
+        self.pending_changes = match self.api_client.as_ref().and_then(|c| c.user_id()) {
+            Some(u) => outbox::entries_for(u).len(),
+            None => 0,
+        };
+    }
+
+    fn api_client_mut(&mut self) -> &mut APIClient {
+        self.api_client
+            .as_mut()
+            .expect("the API client is created once the configuration has loaded")
+    }
+
+    fn is_signed_in(&self) -> bool {
+        self.api_client
+            .as_ref()
+            .map(|c| c.auth_header().is_some())
+            .unwrap_or(false)
+    }
+
+    // Shown until the configuration has loaded, or if it could not be loaded
+    fn boot_view(&self) -> Html {
+        if self.config_task.is_some() {
+            return html! {};
+        }
+        html! {
+            <div class="container">
+                <div class="row">
+                    <div class="col-md-6 offset-md-3 col-xs-12 mt-5">
+                        <h1 class="mb-3">{ "Unable to start" }</h1>
+                        <ErrorAlert<ConfigError> error=&self.config_error />
+                        <p>{ "Please contact the site administrator." }</p>
+                    </div>
+                </div>
+            </div>
+        }
+    }
 }
 
 pub struct AppLoadingTask {
+    id: LoadingTaskId,
     link: ComponentLink<App>,
 }
 
 impl LoadingTask for AppLoadingTask {
     fn set_text(&self, x: Option<String>) {
-        self.link.send_message(Msg::UpdateLoadingText(x));
+        self.link.send_message(Msg::UpdateLoadingText(self.id, x));
+    }
+
+    fn set_on_cancel(&self, x: Option<Callback<()>>) {
+        self.link.send_message(Msg::UpdateLoadingCancel(self.id, x));
+    }
+
+    fn set_progress(&self, x: Option<f64>) {
+        self.link
+            .send_message(Msg::UpdateLoadingProgress(self.id, x));
     }
 }
 
 impl Drop for AppLoadingTask {
     fn drop(&mut self) {
-        self.link.send_message(Msg::StopLoading);
+        self.link.send_message(Msg::StopLoading(self.id));
     }
 }
@@ -1,41 +1,156 @@
-use headers::authorization::Basic;
-use headers::Authorization;
+use crate::api::{AuthHeader, AuthScheme};
 use serde::{Deserialize, Serialize};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{Storage, StorageEvent};
 use yew::format::{Json, Text};
 use yew::services::storage::Area;
 use yew::services::StorageService;
+use yew::Callback;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct PersistedAuth {
     pub user_id: u32,
     token: String,
+    // Milliseconds since the epoch, only set when remembered in local storage
+    #[serde(default)]
+    expires: Option<f64>,
 }
 
-impl From<PersistedAuth> for Authorization<Basic> {
-    fn from(x: PersistedAuth) -> Self {
-        Authorization::basic(&x.user_id.to_string(), &x.token)
-    }
+const KEY: &str = "kiwi_auth";
+// Tells other tabs about signing in or out, since they can't see this tab's session storage
+const SIGNAL_KEY: &str = "kiwi_auth_signal";
+
+// How long "Remember me" keeps the user signed in for
+const REMEMBER_FOR_MILLIS: f64 = 30.0 * 24.0 * 60.0 * 60.0 * 1000.0;
+
+fn storage(area: Area) -> StorageService {
+    StorageService::new(area).expect("storage was disabled by the user")
 }
 
-const KEY: &str = "kiwi_auth";
+fn restore(area: Area) -> Option<PersistedAuth> {
+    let res = storage(area).restore::<Text>(KEY);
+    res.ok().and_then(|x| serde_json::from_str(&x).ok())
+}
 
-fn storage() -> StorageService {
-    StorageService::new(Area::Local).expect("storage was disabled by the user")
+#[derive(Serialize, Deserialize)]
+enum AuthSignal {
+    SignedIn(PersistedAuth),
+    SignedOut,
+}
+
+impl AuthSignal {
+    // Other tabs get a storage event with the value, which is removed straight away so that a
+    // session which isn't remembered doesn't stay in local storage
+    fn send(&self) {
+        let mut local = storage(Area::Local);
+        local.store::<Text>(SIGNAL_KEY, Json(self).into());
+        local.remove(SIGNAL_KEY);
+    }
+
+    // Copies the sending tab's session into this one. Remembered sessions are in local storage,
+    // which is already shared.
+    fn apply(self) {
+        match self {
+            AuthSignal::SignedIn(x) if x.expires.is_none() => {
+                storage(Area::Session).store::<Text>(KEY, Json(&x).into())
+            }
+            _ => storage(Area::Session).remove(KEY),
+        }
+    }
 }
 
 impl PersistedAuth {
+    pub fn auth_header(&self, scheme: AuthScheme) -> AuthHeader {
+        scheme.header(self.user_id, &self.token)
+    }
+
     pub fn load() -> Option<PersistedAuth> {
-        let res = storage().restore::<Text>(KEY);
-        res.ok().and_then(|x| serde_json::from_str(&x).ok())
+        // Session storage only lasts until the browser is closed
+        if let Some(x) = restore(Area::Session) {
+            return Some(x);
+        }
+        let x = restore(Area::Local)?;
+        match x.expires {
+            Some(e) if e > js_sys::Date::now() => Some(x),
+            _ => {
+                storage(Area::Local).remove(KEY);
+                None
+            }
+        }
+    }
+
+    // Who was signed in, for crash reports. This leaves expired sessions in place, and gives None
+    // rather than panicking if storage can't be read.
+    pub fn peek_user_id() -> Option<u32> {
+        let window = web_sys::window()?;
+        let read = |storage: Result<Option<Storage>, JsValue>| {
+            let data = storage.ok()??.get_item(KEY).ok()??;
+            serde_json::from_str::<PersistedAuth>(&data).ok()
+        };
+        read(window.session_storage())
+            .or_else(|| read(window.local_storage()))
+            .map(|x| x.user_id)
     }
 
-    pub fn persist(user_id: u32, token: String) -> PersistedAuth {
-        let x = Self { user_id, token };
-        storage().store::<Text>(KEY, Json(&x).into());
+    pub fn persist(user_id: u32, token: String, remember: bool) -> PersistedAuth {
+        let (area, other, expires) = if remember {
+            let expires = js_sys::Date::now() + REMEMBER_FOR_MILLIS;
+            (Area::Local, Area::Session, Some(expires))
+        } else {
+            (Area::Session, Area::Local, None)
+        };
+        storage(other).remove(KEY);
+        let x = Self {
+            user_id,
+            token,
+            expires,
+        };
+        storage(area).store::<Text>(KEY, Json(&x).into());
+        AuthSignal::SignedIn(x.clone()).send();
         x
     }
 
+    // Signs out every tab
     pub fn remove() {
-        storage().remove(KEY);
+        storage(Area::Session).remove(KEY);
+        storage(Area::Local).remove(KEY);
+        AuthSignal::SignedOut.send();
+    }
+
+    // Notifies when another tab signs in or out, once this tab's storage has been updated to match
+    pub fn watch(callback: Callback<()>) -> AuthWatcher {
+        let f: Box<dyn FnMut(StorageEvent)> = Box::new(move |e: StorageEvent| {
+            match (e.key(), e.new_value()) {
+                (Some(k), Some(v)) if k == SIGNAL_KEY => {
+                    if let Ok(signal) = serde_json::from_str::<AuthSignal>(&v) {
+                        signal.apply();
+                        callback.emit(());
+                    }
+                }
+                // The whole storage was cleared
+                (None, _) => callback.emit(()),
+                _ => {}
+            }
+        });
+        let listener = Closure::wrap(f);
+        web_sys::window()
+            .unwrap()
+            .add_event_listener_with_callback("storage", listener.as_ref().unchecked_ref())
+            .unwrap();
+        AuthWatcher { listener }
+    }
+}
+
+pub struct AuthWatcher {
+    listener: Closure<dyn FnMut(StorageEvent)>,
+}
+
+impl Drop for AuthWatcher {
+    fn drop(&mut self) {
+        web_sys::window()
+            .unwrap()
+            .remove_event_listener_with_callback("storage", self.listener.as_ref().unchecked_ref())
+            .unwrap();
     }
 }
@@ -0,0 +1,759 @@
+// An in-memory imitation of the Kiwi API, so the admin can be developed and tested without a backend
+// cargo run --features mock-server --bin mock-server -- [--port 8002] [--fixtures fixtures.json]
+
+mod multipart;
+mod state;
+
+use serde::Serialize;
+use state::{Fixtures, State};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::process;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+const DEFAULT_PORT: u16 = 8002;
+
+struct Args {
+    port: u16,
+    fixtures: Option<PathBuf>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        port: DEFAULT_PORT,
+        fixtures: None,
+    };
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let value = iter
+            .next()
+            .ok_or_else(|| format!("Missing value for {}", arg))?;
+        match arg.as_str() {
+            "--port" => args.port = value.parse().map_err(|_| "Invalid port".to_string())?,
+            "--fixtures" => args.fixtures = Some(PathBuf::from(value)),
+            _ => return Err(format!("Unknown argument {}", arg)),
+        }
+    }
+    Ok(args)
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        eprintln!("Usage: mock-server [--port PORT] [--fixtures FILE]");
+        process::exit(1);
+    });
+    let mut state = State::new(format!("http://localhost:{}/", args.port));
+    let seeded = match &args.fixtures {
+        None => state.seed(Fixtures::builtin(), Path::new(".")),
+        Some(p) => Fixtures::from_file(p)
+            .and_then(|f| state.seed(f, p.parent().unwrap_or_else(|| Path::new(".")))),
+    };
+    if let Err(e) = seeded {
+        eprintln!("Couldn't load the fixtures: {}", e);
+        process::exit(1);
+    }
+    let server = Server::http(("0.0.0.0", args.port)).unwrap_or_else(|e| {
+        eprintln!("Couldn't start the server: {}", e);
+        process::exit(1);
+    });
+    println!("Mock API listening on {}", state.base_url);
+    for request in server.incoming_requests() {
+        handle(&mut state, request);
+    }
+}
+
+struct MockRequest {
+    method: Method,
+    path: Vec<String>,
+    query: HashMap<String, String>,
+    authorization: Option<String>,
+    content_type: Option<String>,
+    if_match: Option<String>,
+    if_none_match: Option<String>,
+    content_range: Option<String>,
+    body: Vec<u8>,
+}
+
+struct Reply {
+    status: u16,
+    content_type: String,
+    headers: Vec<(&'static str, String)>,
+    body: Vec<u8>,
+}
+
+#[derive(Serialize)]
+struct Details<'a> {
+    code: &'a str,
+    description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fields: Option<HashMap<&'a str, String>>,
+}
+
+impl Reply {
+    fn json<T: Serialize>(status: u16, value: &T) -> Self {
+        Self {
+            status,
+            content_type: "application/json".to_string(),
+            headers: Vec::new(),
+            body: serde_json::to_vec(value).unwrap(),
+        }
+    }
+
+    fn ok<T: Serialize>(value: &T) -> Self {
+        Self::json(200, value)
+    }
+
+    // An item, tagged with the version that updates to it are checked against
+    fn versioned<T: Serialize>(value: &T, version: u32) -> Self {
+        let mut x = Self::ok(value);
+        x.headers.push(("ETag", format!("\"{}\"", version)));
+        x
+    }
+
+    fn error(status: u16, code: &str, description: Option<String>) -> Self {
+        Self::json(
+            status,
+            &Details {
+                code,
+                description,
+                fields: None,
+            },
+        )
+    }
+
+    fn invalid_field(field: &str, message: String) -> Self {
+        let fields = Some(vec![(field, message)].into_iter().collect());
+        Self::json(
+            400,
+            &Details {
+                code: "BAD_REQUEST",
+                description: None,
+                fields,
+            },
+        )
+    }
+
+    fn bad_request(description: String) -> Self {
+        Self::error(400, "BAD_REQUEST", Some(description))
+    }
+
+    fn not_found() -> Self {
+        Self::error(404, "NOT_FOUND", None)
+    }
+}
+
+type Handled = Result<Reply, Reply>;
+
+fn handle(state: &mut State, mut request: Request) {
+    let url = url::Url::parse(&format!("http://localhost{}", request.url())).unwrap();
+    let mut req = MockRequest {
+        method: request.method().clone(),
+        path: url
+            .path_segments()
+            .map(|s| s.filter(|x| !x.is_empty()).map(|x| x.to_string()).collect())
+            .unwrap_or_default(),
+        query: url.query_pairs().into_owned().collect(),
+        authorization: header_value(&request, "Authorization"),
+        content_type: header_value(&request, "Content-Type"),
+        if_match: header_value(&request, "If-Match"),
+        if_none_match: header_value(&request, "If-None-Match"),
+        content_range: header_value(&request, "Content-Range"),
+        body: Vec::new(),
+    };
+    if let Err(e) = request.as_reader().read_to_end(&mut req.body) {
+        eprintln!("Couldn't read the request body: {}", e);
+        return;
+    }
+    let reply = reply(state, &req);
+    println!("{} {} -> {}", req.method, request.url(), reply.status);
+    let mut response = Response::from_data(reply.body)
+        .with_status_code(reply.status)
+        .with_header(header_from("Content-Type", &reply.content_type))
+        .with_header(header_from("Access-Control-Allow-Origin", "*"))
+        .with_header(header_from(
+            "Access-Control-Allow-Headers",
+            "Authorization, Content-Type, Content-Range, If-Match, If-None-Match",
+        ))
+        .with_header(header_from(
+            "Access-Control-Allow-Methods",
+            "GET, POST, PUT, DELETE, OPTIONS",
+        ))
+        .with_header(header_from(
+            "Access-Control-Expose-Headers",
+            "Retry-After, ETag",
+        ));
+    for (name, value) in &reply.headers {
+        response.add_header(header_from(name, value));
+    }
+    if let Err(e) = request.respond(response) {
+        eprintln!("Couldn't send the response: {}", e);
+    }
+}
+
+fn reply(state: &mut State, req: &MockRequest) -> Reply {
+    if req.method == Method::Options {
+        return Reply {
+            status: 204,
+            content_type: "text/plain".to_string(),
+            headers: Vec::new(),
+            body: Vec::new(),
+        };
+    }
+    let reply = route(state, req).unwrap_or_else(|e| e);
+    with_etag(req, reply)
+}
+
+// Tags successful GET responses, so unchanged data doesn't need to be sent again. Items are
+// already tagged with their version, which is what If-Match is checked against.
+fn with_etag(req: &MockRequest, mut reply: Reply) -> Reply {
+    if req.method != Method::Get || reply.status != 200 {
+        return reply;
+    }
+    let etag = match reply.headers.iter().find(|(name, _)| *name == "ETag") {
+        Some((_, etag)) => etag.clone(),
+        None => {
+            let mut hasher = DefaultHasher::new();
+            reply.body.hash(&mut hasher);
+            let etag = format!("\"{:x}\"", hasher.finish());
+            reply.headers.push(("ETag", etag.clone()));
+            etag
+        }
+    };
+    if req.if_none_match.as_deref() == Some(etag.as_str()) {
+        reply.status = 304;
+        reply.body = Vec::new();
+    }
+    reply
+}
+
+fn header_value(request: &Request, name: &'static str) -> Option<String> {
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv(name))
+        .map(|h| h.value.to_string())
+}
+
+fn header_from(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).unwrap()
+}
+
+fn route(state: &mut State, req: &MockRequest) -> Handled {
+    let path: Vec<&str> = req.path.iter().map(|x| x.as_str()).collect();
+    // Endpoints which don't require authentication
+    match (&req.method, path.as_slice()) {
+        (Method::Post, ["sessions", "login"]) => return login(state, req),
+        (Method::Post, ["errors"]) => {
+            println!("Crash report: {}", String::from_utf8_lossy(&req.body));
+            return Ok(Reply::ok(&()));
+        }
+        (Method::Post, ["password_reset", "request"]) => {
+            state.request_password_reset(&field(&form(req)?, "email")?);
+            return Ok(Reply::ok(&()));
+        }
+        (Method::Post, ["password_reset", "submit"]) => {
+            let form = form(req)?;
+            let valid = state.submit_password_reset(
+                &field(&form, "email")?,
+                &field(&form, "token")?,
+                field(&form, "new_password")?,
+            );
+            return if valid {
+                Ok(Reply::ok(&()))
+            } else {
+                Err(Reply::bad_request("Invalid or expired token".to_string()))
+            };
+        }
+        (Method::Get, ["files", name]) => {
+            let file = state.files.get(*name).ok_or_else(Reply::not_found)?;
+            return Ok(Reply {
+                status: 200,
+                content_type: file.content_type.clone(),
+                headers: Vec::new(),
+                body: file.data.clone(),
+            });
+        }
+        _ => {}
+    }
+    let token = authenticate(state, req)?;
+    match (&req.method, path.as_slice()) {
+        (Method::Delete, ["sessions", "logout"]) => {
+            state.sessions.remove(&token);
+            Ok(Reply::ok(&()))
+        }
+        (Method::Get, ["users"]) => users_list(state, req),
+        (Method::Post, ["users"]) => {
+            let form = form(req)?;
+            let (name, email) = (field(&form, "name")?, field(&form, "email")?);
+            validate_user(&name, &email)?;
+            let user = state
+                .create_user(name, email, None)
+                .map_err(|e| Reply::invalid_field("email", e))?;
+            state.request_password_reset(&user.email);
+            Ok(Reply::ok(&user))
+        }
+        (Method::Get, ["users", id]) => {
+            let user = state
+                .user_by_id(parse_id(id)?)
+                .ok_or_else(Reply::not_found)?;
+            Ok(Reply::versioned(user, user.version))
+        }
+        (Method::Put, ["users", id]) => {
+            let id = parse_id(id)?;
+            let form = form(req)?;
+            let email = field(&form, "email")?;
+            validate_user(&field(&form, "name")?, &email)?;
+            if state.users.iter().any(|u| u.email == email && u.id != id) {
+                return Err(Reply::invalid_field(
+                    "email",
+                    "A user with that email address already exists".to_string(),
+                ));
+            }
+            let user = state
+                .users
+                .iter_mut()
+                .find(|u| u.id == id)
+                .ok_or_else(Reply::not_found)?;
+            check_version(req, user.version)?;
+            user.name = field(&form, "name")?;
+            user.email = email;
+            user.version += 1;
+            Ok(Reply::ok(user))
+        }
+        (Method::Delete, ["users", id]) => {
+            if state.delete_user(parse_id(id)?) {
+                Ok(Reply::ok(&()))
+            } else {
+                Err(Reply::not_found())
+            }
+        }
+        (Method::Get, ["gallery", "list"]) => Ok(Reply::ok(&state.gallery_list())),
+        (Method::Post, ["gallery"]) => gallery_create(state, req),
+        (Method::Post, ["gallery", "uploads"]) => {
+            let form = form(req)?;
+            let size = field(&form, "size")?
+                .parse()
+                .map_err(|_| Reply::bad_request("Invalid size".to_string()))?;
+            let id = state
+                .create_upload(field(&form, "filename")?, size)
+                .ok_or_else(|| Reply::error(413, "PAYLOAD_TOO_LARGE", None))?;
+            upload_status(state, &id)
+        }
+        (Method::Get, ["gallery", "uploads", id]) => upload_status(state, id),
+        (Method::Put, ["gallery", "uploads", id]) => {
+            if !state.uploads.contains_key(*id) {
+                return Err(Reply::not_found());
+            }
+            let start = req
+                .content_range
+                .as_deref()
+                .and_then(parse_content_range)
+                .ok_or_else(|| Reply::bad_request("Invalid Content-Range".to_string()))?;
+            state
+                .append_upload(id, start, &req.body)
+                .map_err(Reply::bad_request)?;
+            upload_status(state, id)
+        }
+        (Method::Post, ["gallery", "uploads", id, "finalize"]) => {
+            if !state.uploads.contains_key(*id) {
+                return Err(Reply::not_found());
+            }
+            let form = form(req)?;
+            let description = field(&form, "description")?;
+            validate_description(&description)?;
+            let category = field(&form, "category")?;
+            state::check_category(&category).map_err(|e| Reply::invalid_field("category", e))?;
+            state
+                .finalize_upload(id, description, category)
+                .map_err(Reply::bad_request)?;
+            Ok(Reply::ok(&()))
+        }
+        (Method::Get, ["gallery", id]) => {
+            let item = state
+                .gallery_item(parse_id(id)?)
+                .ok_or_else(Reply::not_found)?;
+            Ok(Reply::versioned(item, item.version))
+        }
+        (Method::Put, ["gallery", id]) => {
+            let id = parse_id(id)?;
+            let item = state.gallery_item(id).ok_or_else(Reply::not_found)?;
+            check_version(req, item.version)?;
+            let form = form(req)?;
+            validate_description(&field(&form, "description")?)?;
+            let move_after_id = match form.get("move_after_id") {
+                None => None,
+                Some(x) => Some(parse_id(x)?),
+            };
+            state
+                .update_gallery_item(
+                    id,
+                    field(&form, "description")?,
+                    field(&form, "category")?,
+                    move_after_id,
+                    form.get("move_to_front").map(|x| x.as_str()) == Some("true"),
+                )
+                .map_err(Reply::bad_request)?;
+            Ok(Reply::ok(&()))
+        }
+        (Method::Delete, ["gallery", id]) => {
+            if state.delete_gallery_item(parse_id(id)?) {
+                Ok(Reply::ok(&()))
+            } else {
+                Err(Reply::not_found())
+            }
+        }
+        _ => Err(Reply::not_found()),
+    }
+}
+
+fn login(state: &mut State, req: &MockRequest) -> Handled {
+    #[derive(Serialize)]
+    struct LoginResponse {
+        token: String,
+        user: state::User,
+    }
+    let form = form(req)?;
+    let email = field(&form, "email")?;
+    if let Some(d) = state.login_locked_for(&email) {
+        let mut reply = Reply::error(429, "TOO_MANY_REQUESTS", None);
+        let secs = d.as_secs() + 1; // Rounded up, so the client doesn't retry too soon
+        reply.headers.push(("Retry-After", secs.to_string()));
+        return Err(reply);
+    }
+    match state.login(&email, &field(&form, "password")?) {
+        Some((token, user)) => Ok(Reply::ok(&LoginResponse { token, user })),
+        None => Err(Reply::error(401, "INCORRECT_CREDENTIALS", None)),
+    }
+}
+
+// The admin sends either a Bearer token, or HTTP Basic auth with the user id as the username and
+// the token as the password, as the app can be configured to use either
+// Returns the session token if it is valid
+fn authenticate(state: &State, req: &MockRequest) -> Result<String, Reply> {
+    let missing = || Reply::error(401, "MISSING_CREDENTIALS", None);
+    let incorrect = || Reply::error(401, "INCORRECT_CREDENTIALS", None);
+    let header = req.authorization.as_ref().ok_or_else(missing)?;
+    if let Some(token) = header.strip_prefix("Bearer ") {
+        if state.sessions.contains_key(token) {
+            return Ok(token.to_string());
+        }
+        return Err(incorrect());
+    }
+    let encoded = header.strip_prefix("Basic ").ok_or_else(missing)?;
+    let decoded = base64::decode(encoded).map_err(|_| missing())?;
+    let decoded = String::from_utf8(decoded).map_err(|_| missing())?;
+    let mut split = decoded.splitn(2, ':');
+    let user_id: u32 = split
+        .next()
+        .and_then(|x| x.parse().ok())
+        .ok_or_else(incorrect)?;
+    let token = split.next().ok_or_else(missing)?;
+    if state.authenticate(user_id, token) {
+        Ok(token.to_string())
+    } else {
+        Err(incorrect())
+    }
+}
+
+fn users_list(state: &State, req: &MockRequest) -> Handled {
+    #[derive(Serialize)]
+    struct Counted<'a> {
+        total: usize,
+        results: Vec<&'a state::User>,
+    }
+    let number = |name: &str, default: usize| match req.query.get(name) {
+        None => Ok(default),
+        Some(x) => x
+            .parse()
+            .map_err(|_| Reply::bad_request(format!("Invalid {}", name))),
+    };
+    let limit = number("limit", 100)?;
+    let offset = number("offset", 0)?;
+    let search = req.query.get("search").map(|s| s.to_lowercase());
+    let matching: Vec<&state::User> = state
+        .users
+        .iter()
+        .filter(|u| match &search {
+            None => true,
+            Some(s) => u.name.to_lowercase().contains(s) || u.email.to_lowercase().contains(s),
+        })
+        .collect();
+    Ok(Reply::ok(&Counted {
+        total: matching.len(),
+        results: matching.into_iter().skip(offset).take(limit).collect(),
+    }))
+}
+
+fn gallery_create(state: &mut State, req: &MockRequest) -> Handled {
+    let content_type = req.content_type.as_deref().unwrap_or("");
+    let parts = multipart::parse(content_type, &req.body)
+        .ok_or_else(|| Reply::bad_request("Invalid multipart body".to_string()))?;
+    let text = |name: &str| {
+        parts
+            .iter()
+            .find(|p| p.name == name)
+            .and_then(|p| String::from_utf8(p.data.clone()).ok())
+            .ok_or_else(|| Reply::bad_request(format!("Missing field {}", name)))
+    };
+    let image = parts
+        .iter()
+        .find(|p| p.name == "image")
+        .ok_or_else(|| Reply::bad_request("Missing field image".to_string()))?;
+    let filename = image.filename.clone().unwrap_or_default();
+    let description = text("description")?;
+    validate_description(&description)?;
+    let category = text("category")?;
+    state::check_category(&category).map_err(|e| Reply::invalid_field("category", e))?;
+    // Anything else wrong is with the image, which doesn't have a field on the form
+    state
+        .create_gallery_item(description, category, Some((image.data.clone(), filename)))
+        .map_err(Reply::bad_request)?;
+    Ok(Reply::ok(&()))
+}
+
+fn upload_status(state: &State, id: &str) -> Handled {
+    #[derive(Serialize)]
+    struct UploadStatus<'a> {
+        id: &'a str,
+        received: usize,
+    }
+    let upload = state.uploads.get(id).ok_or_else(Reply::not_found)?;
+    Ok(Reply::ok(&UploadStatus {
+        id,
+        received: upload.data.len(),
+    }))
+}
+
+// The start of a "bytes start-end/total" range
+fn parse_content_range(header: &str) -> Option<usize> {
+    let range = header.strip_prefix("bytes ")?;
+    range.split('-').next()?.parse().ok()
+}
+
+// Rejects an update if the client was editing an older version, the header is optional
+fn check_version(req: &MockRequest, version: u32) -> Result<(), Reply> {
+    match req.if_match.as_deref().map(|x| x.trim_matches('"')) {
+        Some(v) if v != "*" && v != version.to_string() => {
+            let description = "This was changed by someone else".to_string();
+            Err(Reply::error(409, "CONFLICT", Some(description)))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn validate_user(name: &str, email: &str) -> Result<(), Reply> {
+    if name.trim().is_empty() {
+        return Err(Reply::invalid_field("name", "Name is required".to_string()));
+    }
+    if !email.contains('@') {
+        return Err(Reply::invalid_field(
+            "email",
+            "Not a valid email address".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_description(description: &str) -> Result<(), Reply> {
+    if description.trim().is_empty() {
+        let message = "Description is required".to_string();
+        return Err(Reply::invalid_field("description", message));
+    }
+    Ok(())
+}
+
+fn form(req: &MockRequest) -> Result<HashMap<String, String>, Reply> {
+    serde_urlencoded::from_bytes(&req.body)
+        .map_err(|_| Reply::bad_request("Invalid form body".to_string()))
+}
+
+fn field(form: &HashMap<String, String>, name: &str) -> Result<String, Reply> {
+    form.get(name)
+        .cloned()
+        .ok_or_else(|| Reply::bad_request(format!("Missing field {}", name)))
+}
+
+fn parse_id(id: &str) -> Result<u32, Reply> {
+    id.parse().map_err(|_| Reply::not_found())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The builtin fixtures, with a session for the first user
+    fn signed_in() -> (State, String) {
+        let mut state = State::new("http://mock.test/".to_string());
+        state.seed(Fixtures::builtin(), Path::new(".")).unwrap();
+        let (token, _) = state.login("admin@example.com", "password").unwrap();
+        (state, token)
+    }
+
+    fn request(method: Method, path: &str, token: &str) -> MockRequest {
+        MockRequest {
+            method,
+            path: path.split('/').map(|x| x.to_string()).collect(),
+            query: HashMap::new(),
+            authorization: Some(format!("Bearer {}", token)),
+            content_type: None,
+            if_match: None,
+            if_none_match: None,
+            content_range: None,
+            body: Vec::new(),
+        }
+    }
+
+    fn code(reply: &Reply) -> String {
+        let details: serde_json::Value = serde_json::from_slice(&reply.body).unwrap();
+        details["code"].as_str().unwrap().to_string()
+    }
+
+    fn etag(reply: &Reply) -> Option<&str> {
+        reply
+            .headers
+            .iter()
+            .find(|(name, _)| *name == "ETag")
+            .map(|(_, value)| value.as_str())
+    }
+
+    #[test]
+    fn items_can_be_updated_with_the_etag_they_were_fetched_with() {
+        let (mut state, token) = signed_in();
+        let fetched = reply(&mut state, &request(Method::Get, "users/1", &token));
+        assert_eq!(fetched.status, 200);
+        // The client sends the version from the body, which must match the tag
+        let user: serde_json::Value = serde_json::from_slice(&fetched.body).unwrap();
+        let version = format!("\"{}\"", user["version"]);
+        assert_eq!(etag(&fetched), Some(version.as_str()));
+
+        let mut update = request(Method::Put, "users/1", &token);
+        update.if_match = Some(version.clone());
+        update.body = b"name=Renamed&email=admin%40example.com".to_vec();
+        assert_eq!(reply(&mut state, &update).status, 200);
+        // The same tag is now out of date
+        assert_eq!(reply(&mut state, &update).status, 409);
+
+        let mut refetch = request(Method::Get, "users/1", &token);
+        refetch.if_none_match = Some(version);
+        let refetched = reply(&mut state, &refetch);
+        assert_eq!(refetched.status, 200);
+        update.if_match = etag(&refetched).map(|x| x.to_string());
+        assert_eq!(reply(&mut state, &update).status, 200);
+    }
+
+    #[test]
+    fn gallery_items_are_tagged_with_their_version() {
+        let (mut state, token) = signed_in();
+        let id = state.gallery[0].id;
+        let path = format!("gallery/{}", id);
+        let fetched = reply(&mut state, &request(Method::Get, &path, &token));
+        assert_eq!(etag(&fetched), Some("\"1\""));
+
+        let mut update = request(Method::Put, &path, &token);
+        update.if_match = etag(&fetched).map(|x| x.to_string());
+        update.body = b"description=Oak&category=STAIRCASES&move_to_front=false".to_vec();
+        assert_eq!(reply(&mut state, &update).status, 200);
+        let fetched = reply(&mut state, &request(Method::Get, &path, &token));
+        assert_eq!(etag(&fetched), Some("\"2\""));
+    }
+
+    #[test]
+    fn bearer_and_basic_auth_are_both_accepted() {
+        let (state, token) = signed_in();
+        let mut req = request(Method::Get, "users", &token);
+        assert_eq!(authenticate(&state, &req).ok(), Some(token.clone()));
+
+        let user_id = state.sessions[&token];
+        let credentials = base64::encode(format!("{}:{}", user_id, token));
+        req.authorization = Some(format!("Basic {}", credentials));
+        assert_eq!(authenticate(&state, &req).ok(), Some(token));
+    }
+
+    #[test]
+    fn missing_credentials_are_told_apart_from_incorrect_ones() {
+        let (state, token) = signed_in();
+        let user_id = state.sessions[&token];
+        let rejected = |authorization: Option<String>| {
+            let mut req = request(Method::Get, "users", &token);
+            req.authorization = authorization;
+            let reply = authenticate(&state, &req).unwrap_err();
+            assert_eq!(reply.status, 401);
+            code(&reply)
+        };
+        assert_eq!(rejected(None), "MISSING_CREDENTIALS");
+        assert_eq!(
+            rejected(Some("Digest abc".to_string())),
+            "MISSING_CREDENTIALS"
+        );
+        assert_eq!(
+            rejected(Some("Basic !!!".to_string())),
+            "MISSING_CREDENTIALS"
+        );
+        let no_token = base64::encode(user_id.to_string());
+        assert_eq!(
+            rejected(Some(format!("Basic {}", no_token))),
+            "MISSING_CREDENTIALS"
+        );
+
+        assert_eq!(
+            rejected(Some("Bearer wrong".to_string())),
+            "INCORRECT_CREDENTIALS"
+        );
+        let wrong_user = base64::encode(format!("{}:{}", user_id + 1, token));
+        assert_eq!(
+            rejected(Some(format!("Basic {}", wrong_user))),
+            "INCORRECT_CREDENTIALS"
+        );
+        let wrong_token = base64::encode(format!("{}:wrong", user_id));
+        assert_eq!(
+            rejected(Some(format!("Basic {}", wrong_token))),
+            "INCORRECT_CREDENTIALS"
+        );
+    }
+
+    #[test]
+    fn updates_are_checked_against_the_current_version() {
+        let checked = |if_match: Option<&str>| {
+            let mut req = request(Method::Put, "users/1", "");
+            req.if_match = if_match.map(|x| x.to_string());
+            check_version(&req, 3).map_err(|r| r.status)
+        };
+        assert_eq!(checked(None), Ok(()));
+        assert_eq!(checked(Some("\"3\"")), Ok(()));
+        assert_eq!(checked(Some("3")), Ok(()));
+        assert_eq!(checked(Some("*")), Ok(()));
+        assert_eq!(checked(Some("\"2\"")), Err(409));
+    }
+
+    #[test]
+    fn unchanged_responses_are_not_sent_again() {
+        let body = br#"{"results":[]}"#.to_vec();
+        let ok = || Reply::ok(&serde_json::json!({ "results": [] }));
+        let get = request(Method::Get, "users", "");
+        let tagged = with_etag(&get, ok());
+        assert_eq!(tagged.body, body);
+        let tag = etag(&tagged).unwrap().to_string();
+        assert!(tag.starts_with('"') && tag.ends_with('"'));
+
+        let mut conditional = request(Method::Get, "users", "");
+        conditional.if_none_match = Some(tag.clone());
+        let unchanged = with_etag(&conditional, ok());
+        assert_eq!(unchanged.status, 304);
+        assert!(unchanged.body.is_empty());
+        assert_eq!(etag(&unchanged), Some(tag.as_str()));
+
+        conditional.if_none_match = Some("\"other\"".to_string());
+        let changed = with_etag(&conditional, ok());
+        assert_eq!((changed.status, changed.body), (200, body));
+
+        // Only successful reads are tagged
+        assert_eq!(
+            etag(&with_etag(&request(Method::Put, "users/1", ""), ok())),
+            None
+        );
+        assert_eq!(etag(&with_etag(&get, Reply::not_found())), None);
+    }
+}
@@ -0,0 +1,93 @@
+pub struct Part {
+    pub name: String,
+    pub filename: Option<String>,
+    pub data: Vec<u8>,
+}
+
+// A minimal multipart/form-data parser, enough to read what the admin sends
+pub fn parse(content_type: &str, body: &[u8]) -> Option<Vec<Part>> {
+    let boundary = content_type
+        .split(';')
+        .map(|x| x.trim())
+        .find_map(|x| x.strip_prefix("boundary="))?
+        .trim_matches('"');
+    let delimiter = format!("--{}", boundary).into_bytes();
+    let mut parts = Vec::new();
+    let mut rest = &body[find(body, &delimiter)? + delimiter.len()..];
+    loop {
+        // The closing delimiter has two more hyphens
+        if rest.starts_with(b"--") {
+            return Some(parts);
+        }
+        rest = rest.strip_prefix(b"\r\n")?;
+        let end = find(rest, &delimiter)?;
+        let part = rest[..end].strip_suffix(b"\r\n")?;
+        parts.push(parse_part(part)?);
+        rest = &rest[end + delimiter.len()..];
+    }
+}
+
+fn parse_part(part: &[u8]) -> Option<Part> {
+    let split = find(part, b"\r\n\r\n")?;
+    let headers = std::str::from_utf8(&part[..split]).ok()?;
+    let data = part[split + 4..].to_vec();
+    let mut name = None;
+    let mut filename = None;
+    for header in headers.split("\r\n") {
+        let (key, value) = header.split_at(header.find(':')?);
+        if !key.trim().eq_ignore_ascii_case("content-disposition") {
+            continue;
+        }
+        for param in value[1..].split(';').map(|x| x.trim()) {
+            if let Some(n) = param.strip_prefix("name=") {
+                name = Some(n.trim_matches('"').to_string());
+            } else if let Some(f) = param.strip_prefix("filename=") {
+                filename = Some(f.trim_matches('"').to_string());
+            }
+        }
+    }
+    Some(Part {
+        name: name?,
+        filename,
+        data,
+    })
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT_TYPE: &str = "multipart/form-data; boundary=\"XyZ\"";
+
+    #[test]
+    fn fields_and_files_are_read() {
+        let body = b"preamble\r\n--XyZ\r\n\
+            Content-Disposition: form-data; name=\"description\"\r\n\r\n\
+            Oak stairs\r\n--XyZ\r\n\
+            content-disposition: form-data; name=\"file\"; filename=\"a.jpg\"\r\n\
+            Content-Type: image/jpeg\r\n\r\n\
+            \xff\xd8\r\n\xff\r\n--XyZ--\r\n";
+        let parts = parse(CONTENT_TYPE, body).unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].name, "description");
+        assert_eq!(parts[0].filename, None);
+        assert_eq!(parts[0].data, b"Oak stairs");
+        assert_eq!(parts[1].name, "file");
+        assert_eq!(parts[1].filename.as_deref(), Some("a.jpg"));
+        // Line breaks inside a file are kept
+        assert_eq!(parts[1].data, b"\xff\xd8\r\n\xff");
+    }
+
+    #[test]
+    fn malformed_bodies_are_rejected() {
+        let unterminated = b"--XyZ\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nb";
+        assert!(parse(CONTENT_TYPE, unterminated).is_none());
+        let unnamed = b"--XyZ\r\nContent-Type: text/plain\r\n\r\nb\r\n--XyZ--";
+        assert!(parse(CONTENT_TYPE, unnamed).is_none());
+        assert!(parse("multipart/form-data", b"--XyZ--").is_none());
+    }
+}
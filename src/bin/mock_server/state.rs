@@ -0,0 +1,426 @@
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use std::{fs, iter};
+
+pub const CATEGORIES: [&str; 4] = ["STAIRCASES", "WINDOWS", "DOORS", "OTHER"];
+const MAX_FAILED_LOGINS: u32 = 5;
+const LOGIN_LOCKOUT: Duration = Duration::from_secs(30);
+pub const MAX_UPLOAD_SIZE: usize = 50 * 1024 * 1024;
+
+#[derive(Deserialize)]
+pub struct Fixtures {
+    #[serde(default)]
+    users: Vec<UserFixture>,
+    #[serde(default)]
+    gallery: Vec<GalleryFixture>,
+}
+
+#[derive(Deserialize)]
+struct UserFixture {
+    name: String,
+    email: String,
+    password: String,
+}
+
+#[derive(Deserialize)]
+struct GalleryFixture {
+    description: String,
+    category: String,
+    // Relative to the fixtures file
+    image: Option<String>,
+}
+
+impl Fixtures {
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let data = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        serde_json::from_str(&data).map_err(|e| e.to_string())
+    }
+
+    pub fn builtin() -> Self {
+        serde_json::from_str(include_str!("fixtures.json")).unwrap()
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct User {
+    pub id: u32,
+    pub name: String,
+    pub email: String,
+    pub version: u32, // Incremented on every update
+    #[serde(skip)]
+    pub password: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct GalleryItem {
+    pub id: u32,
+    pub description: String,
+    pub category: String,
+    pub files: Vec<GalleryFile>,
+    pub version: u32, // Incremented on every update
+}
+
+#[derive(Serialize, Clone)]
+pub struct GalleryFile {
+    pub url: String,
+    pub height: usize,
+    pub width: usize,
+    pub bytes: usize,
+}
+
+// A chunked upload which hasn't been finalized yet
+pub struct Upload {
+    pub filename: String,
+    pub size: usize,
+    pub data: Vec<u8>,
+}
+
+pub struct StoredFile {
+    pub data: Vec<u8>,
+    pub content_type: String,
+}
+
+pub struct State {
+    pub base_url: String,
+    next_id: u32,
+    pub users: Vec<User>,
+    pub sessions: HashMap<String, u32>,
+    pub reset_tokens: HashMap<String, String>,
+    pub gallery: Vec<GalleryItem>, // In display order within each category
+    pub files: HashMap<String, StoredFile>,
+    pub uploads: HashMap<String, Upload>,
+    failed_logins: HashMap<String, (u32, Instant)>, // Count and time of the last failure
+}
+
+impl State {
+    pub fn new(base_url: String) -> Self {
+        Self {
+            base_url,
+            next_id: 1,
+            users: Vec::new(),
+            sessions: HashMap::new(),
+            reset_tokens: HashMap::new(),
+            gallery: Vec::new(),
+            files: HashMap::new(),
+            uploads: HashMap::new(),
+            failed_logins: HashMap::new(),
+        }
+    }
+
+    pub fn seed(&mut self, fixtures: Fixtures, dir: &Path) -> Result<(), String> {
+        for u in fixtures.users {
+            self.create_user(u.name, u.email, Some(u.password))?;
+        }
+        for g in fixtures.gallery {
+            let image = match g.image {
+                None => None,
+                Some(p) => {
+                    let path = dir.join(&p);
+                    let data = fs::read(&path).map_err(|e| format!("{}: {}", p, e))?;
+                    Some((data, p))
+                }
+            };
+            self.create_gallery_item(g.description, g.category, image)?;
+        }
+        Ok(())
+    }
+
+    fn next_id(&mut self) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    pub fn user_by_id(&self, id: u32) -> Option<&User> {
+        self.users.iter().find(|u| u.id == id)
+    }
+
+    pub fn create_user(
+        &mut self,
+        name: String,
+        email: String,
+        password: Option<String>,
+    ) -> Result<User, String> {
+        if self.users.iter().any(|u| u.email == email) {
+            return Err("A user with that email address already exists".to_string());
+        }
+        let user = User {
+            id: self.next_id(),
+            name,
+            email,
+            version: 1,
+            password,
+        };
+        self.users.push(user.clone());
+        Ok(user)
+    }
+
+    // How long until the email can be used to log in again, after too many failed attempts
+    pub fn login_locked_for(&self, email: &str) -> Option<Duration> {
+        match self.failed_logins.get(email) {
+            Some((count, at)) if *count >= MAX_FAILED_LOGINS => {
+                LOGIN_LOCKOUT.checked_sub(at.elapsed())
+            }
+            _ => None,
+        }
+    }
+
+    pub fn login(&mut self, email: &str, password: &str) -> Option<(String, User)> {
+        if self.login_locked_for(email).is_some() {
+            return None;
+        }
+        let user = self
+            .users
+            .iter()
+            .find(|u| u.email == email && u.password.as_deref() == Some(password));
+        let user = match user {
+            Some(u) => u.clone(),
+            None => {
+                let failures = self
+                    .failed_logins
+                    .entry(email.to_string())
+                    .or_insert((0, Instant::now()));
+                // Once a lockout has passed, the count starts again
+                let count = if failures.0 >= MAX_FAILED_LOGINS {
+                    1
+                } else {
+                    failures.0 + 1
+                };
+                *failures = (count, Instant::now());
+                return None;
+            }
+        };
+        self.failed_logins.remove(email);
+        let token = random_token();
+        self.sessions.insert(token.clone(), user.id);
+        Some((token, user))
+    }
+
+    pub fn authenticate(&self, user_id: u32, token: &str) -> bool {
+        self.sessions.get(token) == Some(&user_id)
+    }
+
+    pub fn delete_user(&mut self, id: u32) -> bool {
+        let before = self.users.len();
+        self.users.retain(|u| u.id != id);
+        self.sessions.retain(|_, u| *u != id);
+        before != self.users.len()
+    }
+
+    // The real API would email the token, so it is printed to stdout instead
+    pub fn request_password_reset(&mut self, email: &str) {
+        if self.users.iter().any(|u| u.email == email) {
+            let token = random_token();
+            println!(
+                "Password reset for {}: /password_reset?email={}&token={}",
+                email, email, token
+            );
+            self.reset_tokens.insert(email.to_string(), token);
+        }
+    }
+
+    pub fn submit_password_reset(&mut self, email: &str, token: &str, password: String) -> bool {
+        if self.reset_tokens.get(email).map(|t| t.as_str()) != Some(token) {
+            return false;
+        }
+        self.reset_tokens.remove(email);
+        // The user may have been deleted since requesting the reset
+        let user = match self.users.iter_mut().find(|u| u.email == email) {
+            Some(u) => u,
+            None => return false,
+        };
+        user.password = Some(password);
+        let id = user.id;
+        self.sessions.retain(|_, u| *u != id);
+        true
+    }
+
+    pub fn gallery_item(&self, id: u32) -> Option<&GalleryItem> {
+        self.gallery.iter().find(|g| g.id == id)
+    }
+
+    pub fn create_gallery_item(
+        &mut self,
+        description: String,
+        category: String,
+        image: Option<(Vec<u8>, String)>,
+    ) -> Result<GalleryItem, String> {
+        check_category(&category)?;
+        let id = self.next_id();
+        let mut files = Vec::new();
+        if let Some((data, filename)) = image {
+            let size = imagesize::blob_size(&data)
+                .map_err(|e| format!("The image couldn't be read: {}", e))?;
+            let extension = Path::new(&filename)
+                .extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or("jpg")
+                .to_lowercase();
+            let content_type = match extension.as_str() {
+                "png" => "image/png",
+                "gif" => "image/gif",
+                "webp" => "image/webp",
+                _ => "image/jpeg",
+            };
+            let name = format!("{}.{}", id, extension);
+            files.push(GalleryFile {
+                url: format!("{}files/{}", self.base_url, name),
+                height: size.height,
+                width: size.width,
+                bytes: data.len(),
+            });
+            self.files.insert(
+                name,
+                StoredFile {
+                    data,
+                    content_type: content_type.to_string(),
+                },
+            );
+        }
+        let item = GalleryItem {
+            id,
+            description,
+            category,
+            files,
+            version: 1,
+        };
+        self.gallery.push(item.clone());
+        Ok(item)
+    }
+
+    pub fn update_gallery_item(
+        &mut self,
+        id: u32,
+        description: String,
+        category: String,
+        move_after_id: Option<u32>,
+        move_to_front: bool,
+    ) -> Result<(), String> {
+        check_category(&category)?;
+        let index = self
+            .gallery
+            .iter()
+            .position(|g| g.id == id)
+            .ok_or_else(|| "Gallery item not found".to_string())?;
+        let mut item = self.gallery.remove(index);
+        let category_changed = item.category != category;
+        item.description = description;
+        item.category = category;
+        item.version += 1;
+        let position = if move_to_front {
+            self.gallery
+                .iter()
+                .position(|g| g.category == item.category)
+                .unwrap_or(self.gallery.len())
+        } else if let Some(after) = move_after_id {
+            match self.gallery.iter().position(|g| g.id == after) {
+                Some(p) => p + 1,
+                None => {
+                    self.gallery.insert(index, item);
+                    return Err("move_after_id does not exist".to_string());
+                }
+            }
+        } else if category_changed {
+            self.gallery.len()
+        } else {
+            index
+        };
+        self.gallery.insert(position, item);
+        Ok(())
+    }
+
+    pub fn delete_gallery_item(&mut self, id: u32) -> bool {
+        let before = self.gallery.len();
+        self.gallery.retain(|g| g.id != id);
+        before != self.gallery.len()
+    }
+
+    // None if the file is larger than the server accepts
+    pub fn create_upload(&mut self, filename: String, size: usize) -> Option<String> {
+        if size > MAX_UPLOAD_SIZE {
+            return None;
+        }
+        let id = random_token();
+        let upload = Upload {
+            filename,
+            size,
+            data: Vec::new(),
+        };
+        self.uploads.insert(id.clone(), upload);
+        Some(id)
+    }
+
+    // Stores a chunk starting at `start`, which may overlap what has already been received if
+    // the client is retrying a chunk whose response was lost
+    pub fn append_upload(&mut self, id: &str, start: usize, chunk: &[u8]) -> Result<(), String> {
+        let upload = self
+            .uploads
+            .get_mut(id)
+            .ok_or_else(|| "Upload not found".to_string())?;
+        let received = upload.data.len();
+        if start > received {
+            return Err(format!("Expected a chunk starting at {}", received));
+        }
+        if start + chunk.len() > upload.size {
+            return Err("The chunk is past the end of the file".to_string());
+        }
+        if start + chunk.len() > received {
+            upload.data.extend_from_slice(&chunk[received - start..]);
+        }
+        Ok(())
+    }
+
+    pub fn finalize_upload(
+        &mut self,
+        id: &str,
+        description: String,
+        category: String,
+    ) -> Result<GalleryItem, String> {
+        let upload = self
+            .uploads
+            .get(id)
+            .ok_or_else(|| "Upload not found".to_string())?;
+        if upload.data.len() != upload.size {
+            return Err(format!(
+                "Only {} of {} bytes have been received",
+                upload.data.len(),
+                upload.size
+            ));
+        }
+        let image = (upload.data.clone(), upload.filename.clone());
+        let item = self.create_gallery_item(description, category, Some(image))?;
+        self.uploads.remove(id);
+        Ok(item)
+    }
+
+    pub fn gallery_list(&self) -> HashMap<&str, Vec<&GalleryItem>> {
+        let mut map = HashMap::new();
+        for c in CATEGORIES.iter() {
+            map.insert(
+                *c,
+                self.gallery.iter().filter(|g| g.category == *c).collect(),
+            );
+        }
+        map
+    }
+}
+
+pub fn check_category(category: &str) -> Result<(), String> {
+    if CATEGORIES.contains(&category) {
+        Ok(())
+    } else {
+        Err(format!("Unknown category {}", category))
+    }
+}
+
+pub fn random_token() -> String {
+    let mut rng = rand::thread_rng();
+    iter::repeat(())
+        .map(|()| rng.sample(Alphanumeric))
+        .take(32)
+        .collect()
+}
@@ -0,0 +1,46 @@
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{ClipboardEvent, Document, File, FileList};
+use yew::Callback;
+
+fn document() -> Document {
+    web_sys::window().unwrap().document().unwrap()
+}
+
+pub fn files(list: Option<FileList>) -> Vec<File> {
+    match list {
+        Some(l) => (0..l.length()).filter_map(|i| l.get(i)).collect(),
+        None => Vec::new(),
+    }
+}
+
+// Notifies when files, such as a screenshot, are pasted anywhere on the page
+// Pasting text is left alone, so it still works in form fields
+pub struct PasteWatcher {
+    listener: Closure<dyn FnMut(ClipboardEvent)>,
+}
+
+impl PasteWatcher {
+    pub fn new(callback: Callback<Vec<File>>) -> Self {
+        let f: Box<dyn FnMut(ClipboardEvent)> = Box::new(move |e: ClipboardEvent| {
+            let files = files(e.clipboard_data().and_then(|d| d.files()));
+            if !files.is_empty() {
+                e.prevent_default();
+                callback.emit(files);
+            }
+        });
+        let listener = Closure::wrap(f);
+        document()
+            .add_event_listener_with_callback("paste", listener.as_ref().unchecked_ref())
+            .unwrap();
+        Self { listener }
+    }
+}
+
+impl Drop for PasteWatcher {
+    fn drop(&mut self) {
+        document()
+            .remove_event_listener_with_callback("paste", self.listener.as_ref().unchecked_ref())
+            .unwrap();
+    }
+}
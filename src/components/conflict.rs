@@ -0,0 +1,98 @@
+use yew::prelude::*;
+
+// Shown when an update is rejected because someone else changed the same thing, so the user can
+// compare the two versions before choosing which to keep
+pub struct ConflictComponent {
+    props: Props,
+    link: ComponentLink<Self>,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct ConflictField {
+    pub label: &'static str,
+    pub server: String,
+    pub local: String,
+}
+
+#[derive(Properties, Clone, PartialEq)]
+pub struct Props {
+    pub fields: Vec<ConflictField>,
+    pub on_overwrite: Callback<()>,
+    pub on_reload: Callback<()>,
+}
+
+pub enum Msg {
+    Overwrite,
+    Reload,
+}
+
+impl Component for ConflictComponent {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Self { props, link }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::Overwrite => self.props.on_overwrite.emit(()),
+            Msg::Reload => self.props.on_reload.emit(()),
+        }
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        if self.props != props {
+            self.props = props;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn view(&self) -> Html {
+        let onoverwrite = self.link.callback(|_: MouseEvent| Msg::Overwrite);
+        let onreload = self.link.callback(|_: MouseEvent| Msg::Reload);
+        html! {
+            <div class="alert alert-warning" role="alert">
+                <p>{"Someone else saved changes while you were editing:"}</p>
+                <table class="table table-sm bg-white">
+                    <thead>
+                        <tr>
+                            <th scope="col"></th>
+                            <th scope="col">{"Their version"}</th>
+                            <th scope="col">{"Your changes"}</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        { self.props.fields.iter().map(|f| self.row(f)).collect::<Html>() }
+                    </tbody>
+                </table>
+                <button type="button" class="btn btn-danger mr-2" onclick=onoverwrite>
+                    {"Overwrite with mine"}
+                </button>
+                <button type="button" class="btn btn-secondary" onclick=onreload>
+                    {"Discard mine and reload"}
+                </button>
+            </div>
+        }
+    }
+}
+
+impl ConflictComponent {
+    fn row(&self, field: &ConflictField) -> Html {
+        let classes = if field.server != field.local {
+            "table-warning"
+        } else {
+            ""
+        };
+        html! {
+            <tr class=classes>
+                <th scope="row">{field.label}</th>
+                <td>{&field.server}</td>
+                <td>{&field.local}</td>
+            </tr>
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use crate::image_edit::CropRect;
+use imagesize::ImageSize;
+use num_rational::Ratio;
+use wasm_bindgen::JsCast;
+use web_sys::{Element, HtmlElement, PointerEvent};
+use yew::prelude::*;
+
+const MAX_ZOOM: f64 = 4.0;
+
+// Chooses the part of an image to keep, by dragging a frame with a fixed aspect ratio over it
+pub struct CropEditorComponent {
+    props: Props,
+    link: ComponentLink<Self>,
+    image: NodeRef,
+    drag: Option<Drag>,
+}
+
+// Where the pointer was, and where the frame was, when dragging started
+struct Drag {
+    x: i32,
+    y: i32,
+    start: CropRect,
+}
+
+#[derive(Properties, Clone, PartialEq)]
+pub struct Props {
+    pub url: String,
+    pub size: ImageSize,
+    pub aspect: Ratio<usize>,
+    pub crop: CropRect,
+    pub on_change: Callback<CropRect>,
+    #[prop_or_default]
+    pub disabled: bool,
+}
+
+pub enum Msg {
+    DragStart(PointerEvent),
+    DragMove(PointerEvent),
+    DragEnd,
+    Zoom(f64),
+}
+
+impl Component for CropEditorComponent {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Self {
+            props,
+            link,
+            image: NodeRef::default(),
+            drag: None,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        let (width, height) = (self.props.size.width, self.props.size.height);
+        match msg {
+            Msg::DragStart(e) => {
+                if self.props.disabled {
+                    return false;
+                }
+                e.prevent_default();
+                // Keeps receiving moves when the pointer leaves the frame, or the page
+                if let Some(t) = e.target().and_then(|t| t.dyn_into::<Element>().ok()) {
+                    let _ = t.set_pointer_capture(e.pointer_id());
+                }
+                self.drag = Some(Drag {
+                    x: e.client_x(),
+                    y: e.client_y(),
+                    start: self.props.crop,
+                });
+            }
+            Msg::DragMove(e) => {
+                let drag = match &self.drag {
+                    Some(d) => d,
+                    None => return false,
+                };
+                // The image is shown scaled down, so the pointer moves further than one pixel
+                let shown_width = match self.image.cast::<HtmlElement>() {
+                    Some(i) if i.client_width() > 0 => i.client_width() as f64,
+                    _ => return false,
+                };
+                let scale = width as f64 / shown_width;
+                let dx = (e.client_x() - drag.x) as f64 * scale;
+                let dy = (e.client_y() - drag.y) as f64 * scale;
+                let crop = drag.start.moved(dx as i64, dy as i64, width, height);
+                if crop != self.props.crop {
+                    self.props.on_change.emit(crop);
+                }
+            }
+            Msg::DragEnd => self.drag = None,
+            Msg::Zoom(zoom) => {
+                let crop = self
+                    .props
+                    .crop
+                    .zoomed(width, height, self.props.aspect, zoom);
+                self.props.on_change.emit(crop);
+            }
+        }
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        if self.props != props {
+            self.props = props;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn view(&self) -> Html {
+        let size = self.props.size;
+        let crop = self.props.crop;
+        let ondragstart = self.link.callback(Msg::DragStart);
+        let ondragmove = self.link.callback(Msg::DragMove);
+        let ondragend = self.link.callback(|_: PointerEvent| Msg::DragEnd);
+        let onzoom = self
+            .link
+            .callback(|e: InputData| Msg::Zoom(e.value.parse().unwrap_or(1.0)));
+        let zoom = crop.zoom(size.width, size.height, self.props.aspect);
+        let frame_classes = if self.props.disabled {
+            "crop-editor-frame disabled"
+        } else {
+            "crop-editor-frame"
+        };
+        html! {
+            <figure class="crop-editor">
+                <div class="crop-editor-image">
+                    <img ref=self.image.clone() src=self.props.url.clone() alt="Preview" draggable="false" />
+                    <div
+                        class=frame_classes
+                        style=self.frame_style()
+                        onpointerdown=ondragstart
+                        onpointermove=ondragmove
+                        onpointerup=ondragend.clone()
+                        onpointercancel=ondragend>
+                    </div>
+                </div>
+                <div class="form-inline mt-2">
+                    <label class="mr-2">{ "Zoom" }</label>
+                    <input
+                        type="range"
+                        class="custom-range crop-editor-zoom"
+                        min="1"
+                        max=MAX_ZOOM.to_string()
+                        step="0.05"
+                        value=zoom.to_string()
+                        disabled=self.props.disabled
+                        oninput=onzoom
+                        />
+                </div>
+                <figcaption class="figure-caption">
+                    {format!(
+                        "Cropped to {} × {} from {} × {}, drag the frame to choose what is kept",
+                        crop.width, crop.height, size.width, size.height
+                    )}
+                </figcaption>
+            </figure>
+        }
+    }
+}
+
+impl CropEditorComponent {
+    fn frame_style(&self) -> String {
+        let (w, h) = (self.props.size.width as f64, self.props.size.height as f64);
+        let crop = self.props.crop;
+        if w == 0.0 || h == 0.0 {
+            return "display: none".to_string();
+        }
+        format!(
+            "left: {}%; top: {}%; width: {}%; height: {}%",
+            crop.x as f64 / w * 100.0,
+            crop.y as f64 / h * 100.0,
+            crop.width as f64 / w * 100.0,
+            crop.height as f64 / h * 100.0
+        )
+    }
+}
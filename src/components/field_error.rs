@@ -0,0 +1,57 @@
+use crate::api::error::APIError;
+use yew::prelude::*;
+
+// Classes for the inputs of the main forms
+pub const FORM_CONTROL: &str = "form-control form-control-lg";
+
+// Shows the API's validation error for a form field, placed directly after the input
+pub struct FieldError {
+    props: Props,
+}
+
+#[derive(Properties, Clone, PartialEq)]
+pub struct Props {
+    pub error: Option<APIError>,
+    pub field: &'static str,
+}
+
+impl Component for FieldError {
+    type Message = ();
+    type Properties = Props;
+
+    fn create(props: Self::Properties, _link: ComponentLink<Self>) -> Self {
+        Self { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        if self.props != props {
+            self.props = props;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn view(&self) -> Html {
+        match field_error(&self.props.error, self.props.field) {
+            Some(e) => html! { <div class="invalid-feedback d-block">{e}</div> },
+            None => html! {},
+        }
+    }
+}
+
+fn field_error<'a>(error: &'a Option<APIError>, field: &str) -> Option<&'a str> {
+    error.as_ref().and_then(|e| e.field_error(field))
+}
+
+// Input classes, with the field highlighted if it was rejected
+pub fn input_classes(classes: &str, error: &Option<APIError>, field: &str) -> String {
+    match field_error(error, field) {
+        Some(_) => format!("{} is-invalid", classes),
+        None => classes.to_string(),
+    }
+}
@@ -1,15 +1,14 @@
-use crate::api::error::APIError;
+use crate::api::transport::RequestTask;
 use crate::api::users::UserResponseItem;
 use crate::api::APIClient;
 use crate::loader_task::LoadingFunction;
 use crate::routes::{AppRoute, RouterAnchor};
 use yew::prelude::*;
-use yew::services::fetch::FetchTask;
 
 pub struct HeaderComponent {
     props: Props,
     link: ComponentLink<Self>,
-    logout_task: Option<FetchTask>,
+    logout_task: Option<RequestTask>,
 }
 
 #[derive(Properties, Clone, PartialEq)]
@@ -19,11 +18,13 @@ pub struct Props {
     pub on_logout: Callback<()>,
     pub on_loading: LoadingFunction,
     pub api_client: APIClient,
+    // Changes in the outbox, waiting to be sent
+    pub pending_changes: usize,
 }
 
 pub enum Msg {
     Logout,
-    LogoutResult(Result<(), APIError>),
+    LogoutResult,
 }
 
 impl Component for HeaderComponent {
@@ -43,10 +44,10 @@ impl Component for HeaderComponent {
             Msg::Logout => {
                 self.logout_task = Some(self.props.api_client.session_logout(
                     self.props.on_loading.clone(),
-                    self.link.callback(Msg::LogoutResult),
+                    self.link.callback(|_| Msg::LogoutResult),
                 ))
             }
-            Msg::LogoutResult(_) => {
+            Msg::LogoutResult => {
                 self.props.on_logout.emit(());
             }
         }
@@ -80,6 +81,7 @@ impl Component for HeaderComponent {
                         if self.props.is_signed_in {
                             html!{
                                 <ul class="nav">
+                                    { self.pending_changes_view() }
                                     <li class="nav-item">
                                         <p class="nav-text">{name}</p>
                                     </li>
@@ -98,3 +100,26 @@ impl Component for HeaderComponent {
         }
     }
 }
+
+impl HeaderComponent {
+    fn pending_changes_view(&self) -> Html {
+        let n = self.props.pending_changes;
+        if n == 0 {
+            return html! {};
+        }
+        let text = if n == 1 {
+            "1 unsent change".to_string()
+        } else {
+            format!("{} unsent changes", n)
+        };
+        html! {
+            <li class="nav-item mr-3">
+                <span
+                    class="badge badge-warning"
+                    title="These will be sent once the connection is back">
+                    {text}
+                </span>
+            </li>
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use yew::prelude::*;
+
+pub struct IdleWarningComponent {
+    props: Props,
+    link: ComponentLink<Self>,
+}
+
+#[derive(Properties, Clone, PartialEq)]
+pub struct Props {
+    pub seconds_remaining: u32,
+    pub on_stay: Callback<()>,
+}
+
+pub enum Msg {
+    Stay,
+}
+
+impl Component for IdleWarningComponent {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Self { props, link }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::Stay => self.props.on_stay.emit(()),
+        }
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        if self.props != props {
+            self.props = props;
+            true
+        } else {
+            false
+        }
+    }
+
+    // Displayed without the bootstrap javascript, since it is shown and hidden by the app
+    fn view(&self) -> Html {
+        let onstay = self.link.callback(|_: MouseEvent| Msg::Stay);
+        html! {
+            <>
+                <div class="modal d-block" tabindex="-1" role="dialog">
+                    <div class="modal-dialog" role="document">
+                        <div class="modal-content">
+                            <div class="modal-header">
+                                <h5 class="modal-title">{"Are you still there?"}</h5>
+                            </div>
+                            <div class="modal-body">
+                                <p>{format!("You will be logged out in {}s", self.props.seconds_remaining)}</p>
+                            </div>
+                            <div class="modal-footer">
+                                <button type="button" class="btn btn-primary" onclick=onstay>
+                                    {"Stay signed in"}
+                                </button>
+                            </div>
+                        </div>
+                    </div>
+                </div>
+                <div class="modal-backdrop show"></div>
+            </>
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use imagesize::ImageSize;
+use num_rational::Ratio;
+use web_sys::{Blob, Url};
+use yew::prelude::*;
+
+// A URL for showing data held in memory, which is released once dropped
+pub struct ObjectUrl(String);
+
+impl ObjectUrl {
+    pub fn new(data: &[u8]) -> Option<Self> {
+        let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(data));
+        let blob = Blob::new_with_u8_array_sequence(&parts).ok()?;
+        Url::create_object_url_with_blob(&blob).ok().map(ObjectUrl)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Drop for ObjectUrl {
+    fn drop(&mut self) {
+        let _ = Url::revoke_object_url(&self.0);
+    }
+}
+
+pub fn format_bytes(bytes: usize) -> String {
+    if bytes >= 1024 * 1024 {
+        format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+    } else if bytes >= 1024 {
+        format!("{} KB", bytes / 1024)
+    } else {
+        format!("{} bytes", bytes)
+    }
+}
+
+// Shows an image before it is uploaded, with the part the public site will show outlined
+pub struct ImagePreviewComponent {
+    props: Props,
+}
+
+#[derive(Properties, Clone, PartialEq)]
+pub struct Props {
+    pub url: String,
+    pub size: ImageSize,
+    pub bytes: usize,
+    pub frame_aspect: Ratio<usize>,
+}
+
+impl Component for ImagePreviewComponent {
+    type Message = ();
+    type Properties = Props;
+
+    fn create(props: Self::Properties, _link: ComponentLink<Self>) -> Self {
+        Self { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        if self.props != props {
+            self.props = props;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn view(&self) -> Html {
+        let size = self.props.size;
+        html! {
+            <figure class="image-preview">
+                <div class="image-preview-image">
+                    <img src=self.props.url.clone() alt="Preview" />
+                    <div class="image-preview-frame" style=self.frame_style()></div>
+                </div>
+                <figcaption class="figure-caption">
+                    {format!(
+                        "{} × {} · {} · {} aspect ratio",
+                        size.width,
+                        size.height,
+                        format_bytes(self.props.bytes),
+                        aspect_label(size),
+                    )}
+                </figcaption>
+            </figure>
+        }
+    }
+}
+
+impl ImagePreviewComponent {
+    // The largest centred area with the frame's aspect ratio, as percentages of the image
+    fn frame_style(&self) -> String {
+        let (w, h) = (self.props.size.width as f64, self.props.size.height as f64);
+        let aspect =
+            *self.props.frame_aspect.numer() as f64 / *self.props.frame_aspect.denom() as f64;
+        if w == 0.0 || h == 0.0 {
+            return "display: none".to_string();
+        }
+        let (width, height) = if w / h > aspect {
+            (h * aspect / w * 100.0, 100.0)
+        } else {
+            (100.0, w / aspect / h * 100.0)
+        };
+        format!(
+            "left: {}%; top: {}%; width: {}%; height: {}%",
+            (100.0 - width) / 2.0,
+            (100.0 - height) / 2.0,
+            width,
+            height
+        )
+    }
+}
+
+// Such as "4:3", or "1.33:1" when the ratio doesn't reduce to small numbers
+fn aspect_label(size: ImageSize) -> String {
+    if size.height == 0 {
+        return "unknown".to_string();
+    }
+    let ratio = Ratio::new(size.width, size.height);
+    if *ratio.numer() <= 32 && *ratio.denom() <= 32 {
+        format!("{}:{}", ratio.numer(), ratio.denom())
+    } else {
+        format!("{:.2}:1", size.width as f64 / size.height as f64)
+    }
+}
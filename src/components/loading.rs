@@ -1,3 +1,4 @@
+use crate::loader_task::{LoadingTaskId, PendingTask};
 use std::time::Duration;
 use yew::prelude::*;
 use yew::services::timeout::TimeoutTask;
@@ -16,12 +17,13 @@ pub struct LoadingComponent {
 pub struct LoadingProps {
     pub active: bool,
     pub delay_full_appearance: bool,
-    pub text: Option<String>,
+    pub tasks: Vec<PendingTask>,
 }
 
 pub enum Msg {
     EnableFullyVisible,
     DisableFullyVisible,
+    Cancel(LoadingTaskId),
 }
 
 // (Only applies if activated with delay_full_appearance)
@@ -59,6 +61,13 @@ impl Component for LoadingComponent {
                 self.fully_visible = false;
                 self.became_fully_visible = None;
             }
+            Msg::Cancel(id) => {
+                let task = self.props.tasks.iter().find(|t| t.id == id);
+                if let Some(c) = task.and_then(|t| t.on_cancel.as_ref()) {
+                    c.emit(())
+                }
+                return false;
+            }
         }
         true
     }
@@ -81,12 +90,9 @@ impl Component for LoadingComponent {
                     html!{
                         <div id="loading-component">
                             <div id="loading-component-spinner"></div>
-                            {
-                                match &self.props.text {
-                                    Some(t) => html!{<p>{t}</p>},
-                                    None => html!{}
-                                }
-                            }
+                            <div id="loading-component-tasks">
+                                { self.props.tasks.iter().map(|t| self.task_view(t)).collect::<Html>() }
+                            </div>
                         </div>
                     }
                 } else if self.props.active {
@@ -102,6 +108,58 @@ impl Component for LoadingComponent {
 }
 
 impl LoadingComponent {
+    // When several tasks are pending each is listed, so that it is clear what is still running
+    fn task_view(&self, task: &PendingTask) -> Html {
+        let text = match &task.text {
+            Some(t) => Some(t.as_str()),
+            None if self.props.tasks.len() > 1 => Some("Loading…"),
+            None => None,
+        };
+        let id = task.id;
+        html! {
+            <div class="loading-component-task">
+                {
+                    match text {
+                        Some(t) => html!{<p>{t}</p>},
+                        None => html!{}
+                    }
+                }
+                {
+                    match task.progress {
+                        Some(p) => Self::progress_bar(p),
+                        None => html!{}
+                    }
+                }
+                {
+                    if task.on_cancel.is_some() {
+                        let oncancel = self.link.callback(move |_: MouseEvent| Msg::Cancel(id));
+                        html!{
+                            <button class="btn btn-light btn-sm" onclick=oncancel>{"Cancel"}</button>
+                        }
+                    } else {
+                        html!{}
+                    }
+                }
+            </div>
+        }
+    }
+
+    fn progress_bar(progress: f64) -> Html {
+        let percent = (progress.clamp(0.0, 1.0) * 100.0) as u32;
+        html! {
+            <div class="progress">
+                <div
+                    class="progress-bar"
+                    role="progressbar"
+                    style=format!("width: {}%", percent)
+                    aria-valuenow=percent
+                    aria-valuemin="0"
+                    aria-valuemax="100">
+                </div>
+            </div>
+        }
+    }
+
     fn on_props_change(&mut self, old: LoadingProps) {
         // When activated
         if !old.active && self.props.active {
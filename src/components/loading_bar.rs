@@ -0,0 +1,45 @@
+use yew::prelude::*;
+
+// A thin bar across the top of the page, shown while background tasks are running
+// Unlike the loading component it doesn't stop the user from doing anything else
+pub struct LoadingBarComponent {
+    props: Props,
+}
+
+#[derive(Properties, Clone, PartialEq)]
+pub struct Props {
+    pub active: bool,
+}
+
+impl Component for LoadingBarComponent {
+    type Message = ();
+    type Properties = Props;
+
+    fn create(props: Self::Properties, _link: ComponentLink<Self>) -> Self {
+        Self { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        if self.props != props {
+            self.props = props;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn view(&self) -> Html {
+        if !self.props.active {
+            return html! {};
+        }
+        html! {
+            <div id="loading-bar" class="progress" role="status" aria-label="Loading">
+                <div class="progress-bar progress-bar-striped progress-bar-animated"></div>
+            </div>
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use std::time::Duration;
+use yew::prelude::*;
+use yew::services::interval::{IntervalService, IntervalTask};
+
+// Counts down until the user can try again, after being rate limited by the API
+// Nothing is shown when not locked out
+pub struct LockoutAlert {
+    props: Props,
+    link: ComponentLink<Self>,
+    until: Option<f64>, // Milliseconds since the epoch
+    _interval: Option<IntervalTask>,
+}
+
+#[derive(Properties, Clone, PartialEq)]
+pub struct Props {
+    // From the API error, counted from when it is first given to the component
+    pub retry_after: Option<Duration>,
+    #[prop_or_default]
+    pub on_expired: Callback<()>,
+}
+
+pub enum Msg {
+    Tick,
+}
+
+impl Component for LockoutAlert {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let mut x = Self {
+            props,
+            link,
+            until: None,
+            _interval: None,
+        };
+        x.start();
+        x
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::Tick => {
+                if self.seconds_remaining() == 0 {
+                    self.until = None;
+                    self._interval = None;
+                    self.props.on_expired.emit(());
+                }
+            }
+        }
+        true
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        if self.props != props {
+            let restart = self.props.retry_after != props.retry_after;
+            self.props = props;
+            if restart {
+                self.start();
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    fn view(&self) -> Html {
+        if self.until.is_none() {
+            return html! {};
+        }
+        html! {
+            <div class="alert alert-warning" role="alert">
+                { format!("Too many attempts, please try again in {}s", self.seconds_remaining()) }
+            </div>
+        }
+    }
+}
+
+impl LockoutAlert {
+    fn start(&mut self) {
+        self.until = self
+            .props
+            .retry_after
+            .map(|d| js_sys::Date::now() + d.as_millis() as f64);
+        self._interval = self.until.map(|_| {
+            IntervalService::spawn(Duration::from_secs(1), self.link.callback(|_| Msg::Tick))
+        });
+    }
+
+    fn seconds_remaining(&self) -> u64 {
+        match self.until {
+            Some(until) => ((until - js_sys::Date::now()) / 1000.0).ceil().max(0.0) as u64,
+            None => 0,
+        }
+    }
+}
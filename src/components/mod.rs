@@ -1,8 +1,16 @@
+pub mod conflict;
+pub mod crop_editor;
 pub mod enum_selector;
 pub mod error;
+pub mod field_error;
 pub mod footer;
 pub mod header;
+pub mod idle_warning;
+pub mod image_preview;
 pub mod loading;
+pub mod loading_bar;
+pub mod lockout;
+pub mod outbox_alert;
 pub mod pagination;
 pub mod search;
 pub mod sidebar;
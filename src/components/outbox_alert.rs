@@ -0,0 +1,83 @@
+use crate::api::error::APIError;
+use yew::prelude::*;
+
+// Reports changes from the outbox which the API rejected when they were sent again
+pub struct OutboxAlertComponent {
+    props: Props,
+    link: ComponentLink<Self>,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct OutboxFailure {
+    pub description: String,
+    pub error: APIError,
+}
+
+#[derive(Properties, Clone, PartialEq)]
+pub struct Props {
+    pub failures: Vec<OutboxFailure>,
+    pub on_dismiss: Callback<()>,
+}
+
+pub enum Msg {
+    Dismiss,
+}
+
+impl Component for OutboxAlertComponent {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Self { props, link }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::Dismiss => self.props.on_dismiss.emit(()),
+        }
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        if self.props != props {
+            self.props = props;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn view(&self) -> Html {
+        if self.props.failures.is_empty() {
+            return html! {};
+        }
+        let ondismiss = self.link.callback(|_: MouseEvent| Msg::Dismiss);
+        html! {
+            <div class="container-fluid">
+                <div class="alert alert-warning" role="alert">
+                    <p>{"Some changes made while offline couldn't be saved:"}</p>
+                    <ul>
+                        { self.props.failures.iter().map(Self::failure).collect::<Html>() }
+                    </ul>
+                    <button type="button" class="btn btn-secondary btn-sm" onclick=ondismiss>
+                        {"Dismiss"}
+                    </button>
+                </div>
+            </div>
+        }
+    }
+}
+
+impl OutboxAlertComponent {
+    fn failure(failure: &OutboxFailure) -> Html {
+        let reason = match &failure.error {
+            APIError::Conflict(_) => {
+                "someone else changed it in the meantime, please make the change again".to_string()
+            }
+            e => e.to_string(),
+        };
+        html! {
+            <li>{format!("{}: {}", failure.description, reason)}</li>
+        }
+    }
+}
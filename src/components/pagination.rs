@@ -122,7 +122,7 @@ impl PaginationComponent {
             (0, end)
         } else if pivot + rhs > last_page {
             let lhs = lhs + rhs - (last_page - pivot);
-            let start = if lhs > pivot { 0 } else { pivot - lhs };
+            let start = pivot.saturating_sub(lhs);
             (start, last_page)
         } else {
             let start = pivot - lhs;
@@ -152,10 +152,8 @@ impl PaginationComponent {
             ItemType::Regular(idx) => Some(idx),
             ItemType::Ellipsis => None,
         };
-        let is_active = match item_type {
-            ItemType::Regular(idx) if idx == self.props.current_page => true,
-            _ => false,
-        };
+        let is_active =
+            matches!(item_type, ItemType::Regular(idx) if idx == self.props.current_page);
         let page_name = match item_type {
             ItemType::Previous => "«".to_string(),
             ItemType::Next => "»".to_string(),
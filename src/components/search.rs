@@ -45,7 +45,7 @@ impl Component for SearchBarComponent {
                 ));
             }
             Msg::OnTimer => {
-                let s = if self.value.len() > 0 {
+                let s = if !self.value.is_empty() {
                     Some(self.value.clone())
                 } else {
                     None
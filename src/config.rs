@@ -0,0 +1,73 @@
+use crate::api::{AuthScheme, DEFAULT_MAX_RETRIES};
+use http::{Request, Response, StatusCode};
+use serde::Deserialize;
+use std::str;
+use thiserror::Error;
+use url::Url;
+use yew::format::{Binary, Nothing};
+use yew::services::fetch::{FetchService, FetchTask};
+use yew::Callback;
+
+// Served alongside index.html, so the same build can be deployed to any environment
+const CONFIG_PATH: &str = "/config.json";
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub api_url: Url,
+    // How many times a request which failed temporarily is retried
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    // Either "basic" or "bearer"
+    #[serde(default)]
+    pub auth_scheme: AuthScheme,
+    // Signed in users are logged out after this many minutes without activity, 0 disables this
+    #[serde(default = "default_idle_logout_mins")]
+    pub idle_logout_mins: u32,
+    // Where crash reports are sent, if anywhere
+    #[serde(default)]
+    pub error_report_url: Option<Url>,
+    // Large images are uploaded in chunks, which needs the API's gallery/uploads endpoints
+    #[serde(default)]
+    pub chunked_uploads: bool,
+}
+
+fn default_max_retries() -> u32 {
+    DEFAULT_MAX_RETRIES
+}
+
+fn default_idle_logout_mins() -> u32 {
+    30
+}
+
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum ConfigError {
+    #[error("The configuration file could not be loaded (HTTP {})", .0.as_u16())]
+    Missing(StatusCode),
+    #[error("The configuration file is invalid: {0}")]
+    Invalid(String),
+    #[error("Request for the configuration file failed, please check your connection")]
+    RequestError,
+}
+
+impl Config {
+    pub fn load(callback: Callback<Result<Config, ConfigError>>) -> FetchTask {
+        let request = Request::get(CONFIG_PATH).body(Nothing).unwrap();
+        let handler = move |response: Response<Binary>| {
+            callback.emit(parse(response));
+        };
+        FetchService::fetch_binary(request, Callback::once(handler)).unwrap()
+    }
+}
+
+fn parse(response: Response<Binary>) -> Result<Config, ConfigError> {
+    if let (meta, Ok(bin)) = response.into_parts() {
+        if !meta.status.is_success() {
+            return Err(ConfigError::Missing(meta.status));
+        }
+        let data = str::from_utf8(&bin).map_err(|e| ConfigError::Invalid(e.to_string()))?;
+        serde_json::from_str(data).map_err(|e| ConfigError::Invalid(e.to_string()))
+    } else {
+        Err(ConfigError::RequestError)
+    }
+}
@@ -0,0 +1,150 @@
+use crate::auth::PersistedAuth;
+use crate::routes::{AppRoute, RouteService};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::panic;
+use url::Url;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::console;
+use yew_router::Switch;
+
+// How many log lines are kept, to be sent along with a crash report
+const RECENT_LOG_LINES: usize = 50;
+
+const CRASH_SCREEN: &str = r#"
+<div class="container">
+    <div class="row">
+        <div class="col-md-6 offset-md-3 col-xs-12 mt-5">
+            <h1 class="mb-3">Something went wrong</h1>
+            <p>Sorry, the app has stopped working. Any unsaved changes have been lost.</p>
+            <button id="crash-reload" class="btn btn-lg btn-primary">Reload</button>
+        </div>
+    </div>
+</div>
+"#;
+
+thread_local! {
+    static RECENT_LOGS: RefCell<VecDeque<String>> = const { RefCell::new(VecDeque::new()) };
+    static REPORT_URL: RefCell<Option<Url>> = const { RefCell::new(None) };
+}
+
+#[derive(Serialize)]
+struct CrashReport {
+    message: String,
+    route: String,
+    app_route: Option<String>,
+    user_id: Option<u32>,
+    user_agent: Option<String>,
+    recent_logs: Vec<String>,
+}
+
+// Logs to the browser console, keeping the most recent lines in memory
+struct RecentLogger;
+
+impl Log for RecentLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Debug
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!(
+            "{} {}: {}",
+            record.level(),
+            record.module_path().unwrap_or_default(),
+            record.args()
+        );
+        let value = JsValue::from_str(&line);
+        match record.level() {
+            Level::Error => console::error_1(&value),
+            Level::Warn => console::warn_1(&value),
+            Level::Info => console::info_1(&value),
+            Level::Debug | Level::Trace => console::debug_1(&value),
+        }
+        RECENT_LOGS.with(|l| {
+            let mut l = l.borrow_mut();
+            if l.len() == RECENT_LOG_LINES {
+                l.pop_front();
+            }
+            l.push_back(line);
+        });
+    }
+
+    fn flush(&self) {}
+}
+
+pub fn init_logger() {
+    log::set_logger(&RecentLogger).unwrap();
+    log::set_max_level(LevelFilter::Debug);
+}
+
+// Replaces the app with an error screen when it panics, and reports the crash
+pub fn set_panic_hook() {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        previous(info);
+        show_crash_screen();
+        send_report(info.to_string());
+    }));
+}
+
+// Where crash reports are sent, none are sent if this isn't configured
+pub fn set_report_url(url: Option<Url>) {
+    REPORT_URL.with(|u| *u.borrow_mut() = url);
+}
+
+fn show_crash_screen() {
+    let document = web_sys::window().unwrap().document().unwrap();
+    let body = match document.body() {
+        Some(b) => b,
+        None => return,
+    };
+    body.set_inner_html(CRASH_SCREEN);
+    if let Some(button) = document.get_element_by_id("crash-reload") {
+        let f: Box<dyn FnMut()> = Box::new(|| {
+            let _ = web_sys::window().unwrap().location().reload();
+        });
+        let listener = Closure::wrap(f);
+        let _ = button.add_event_listener_with_callback("click", listener.as_ref().unchecked_ref());
+        // The app has stopped, so there is nothing which could remove the listener
+        listener.forget();
+    }
+}
+
+fn send_report(message: String) {
+    let url = match REPORT_URL.with(|u| u.borrow().clone()) {
+        Some(u) => u,
+        None => return,
+    };
+    let route = RouteService::new().get_route();
+    let app_route = AppRoute::switch(route.clone()).map(|r| format!("{:?}", r));
+    let window = match web_sys::window() {
+        Some(w) => w,
+        None => return,
+    };
+    let report = CrashReport {
+        message,
+        route: route.route,
+        app_route,
+        user_id: PersistedAuth::peek_user_id(),
+        user_agent: window.navigator().user_agent().ok(),
+        recent_logs: RECENT_LOGS.with(|l| l.borrow().iter().cloned().collect()),
+    };
+    let body = match serde_json::to_string(&report) {
+        Ok(b) => b,
+        Err(_) => return,
+    };
+    // A beacon is sent even though the app has stopped, as a plain text body to avoid a CORS
+    // preflight request
+    if let Err(e) = window
+        .navigator()
+        .send_beacon_with_opt_str(url.as_str(), Some(&body))
+    {
+        console::error_2(&JsValue::from_str("Couldn't send the crash report"), &e);
+    }
+}
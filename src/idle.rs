@@ -0,0 +1,86 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{Document, Storage};
+
+const ACTIVITY_EVENTS: [&str; 5] = ["mousemove", "mousedown", "keydown", "touchstart", "scroll"];
+
+// Shared between tabs, so that an idle tab doesn't log out one which is being used
+const KEY: &str = "kiwi_last_activity";
+const SHARE_INTERVAL_MILLIS: f64 = 5000.0;
+
+// Records when the user last interacted with the page
+// Events fire very frequently, so this just stores the time rather than sending a message
+pub struct ActivityTracker {
+    last_activity: Rc<Cell<f64>>,
+    last_shared: Rc<Cell<f64>>,
+    listener: Closure<dyn FnMut()>,
+}
+
+fn document() -> Document {
+    web_sys::window().unwrap().document().unwrap()
+}
+
+fn storage() -> Option<Storage> {
+    web_sys::window().unwrap().local_storage().ok().flatten()
+}
+
+fn record(last_activity: &Cell<f64>, last_shared: &Cell<f64>) {
+    let now = js_sys::Date::now();
+    last_activity.set(now);
+    if now - last_shared.get() >= SHARE_INTERVAL_MILLIS {
+        last_shared.set(now);
+        if let Some(s) = storage() {
+            let _ = s.set_item(KEY, &now.to_string());
+        }
+    }
+}
+
+impl ActivityTracker {
+    pub fn new() -> Self {
+        let last_activity = Rc::new(Cell::new(0.0));
+        let last_shared = Rc::new(Cell::new(0.0));
+        record(&last_activity, &last_shared);
+        let (a, s) = (last_activity.clone(), last_shared.clone());
+        let f: Box<dyn FnMut()> = Box::new(move || record(&a, &s));
+        let listener = Closure::wrap(f);
+        let document = document();
+        for e in ACTIVITY_EVENTS.iter() {
+            document
+                .add_event_listener_with_callback(e, listener.as_ref().unchecked_ref())
+                .unwrap();
+        }
+        Self {
+            last_activity,
+            last_shared,
+            listener,
+        }
+    }
+
+    pub fn record(&self) {
+        // Explicit activity is always shared, so other tabs see it straight away
+        self.last_shared.set(0.0);
+        record(&self.last_activity, &self.last_shared);
+    }
+
+    // Time since the user last interacted with this or any other tab
+    pub fn idle_millis(&self) -> f64 {
+        let shared = storage()
+            .and_then(|s| s.get_item(KEY).ok().flatten())
+            .and_then(|v| v.parse::<f64>().ok())
+            .unwrap_or(0.0);
+        js_sys::Date::now() - self.last_activity.get().max(shared)
+    }
+}
+
+impl Drop for ActivityTracker {
+    fn drop(&mut self) {
+        let document = document();
+        for e in ACTIVITY_EVENTS.iter() {
+            document
+                .remove_event_listener_with_callback(e, self.listener.as_ref().unchecked_ref())
+                .unwrap();
+        }
+    }
+}
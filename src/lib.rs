@@ -5,8 +5,13 @@ mod api;
 mod app;
 mod auth;
 mod bindings;
+mod clipboard;
 mod components;
+mod config;
+mod crash;
 mod form_data;
+mod idle;
+mod image_edit;
 mod loader_task;
 mod routes;
 
@@ -18,7 +23,9 @@ static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
 #[wasm_bindgen(start)]
 pub fn run_app() {
-    wasm_logger::init(wasm_logger::Config::default());
+    crash::init_logger();
     log::info!("Starting app");
-    yew::start_app::<App>();
+    yew::initialize();
+    crash::set_panic_hook();
+    yew::App::<App>::new().mount_to_body();
 }
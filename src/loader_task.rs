@@ -1,8 +1,13 @@
 use std::ops::Deref;
 use std::rc::Rc;
+use yew::Callback;
 
 pub trait LoadingTask {
     fn set_text(&self, x: Option<String>);
+    // If set the user is offered a button to cancel whatever is being waited for
+    fn set_on_cancel(&self, x: Option<Callback<()>>);
+    // Shows a progress bar, from 0.0 to 1.0, for tasks where it is known how much has been done
+    fn set_progress(&self, x: Option<f64>);
 }
 
 pub type BoxedLoadingTask = Box<dyn LoadingTask>;
@@ -16,6 +21,16 @@ impl PartialEq for LoadingFunction {
     }
 }
 
+impl LoadingFunction {
+    // The same loader, but its tasks are shown without blocking the UI
+    pub fn background(&self) -> LoadingFunction {
+        let f = self.0.clone();
+        LoadingFunction(Rc::new(move |cfg: LoadingTaskConfig| {
+            f(cfg.background(true))
+        }))
+    }
+}
+
 impl Deref for LoadingFunction {
     type Target = Rc<dyn Fn(LoadingTaskConfig) -> BoxedLoadingTask>;
 
@@ -26,12 +41,14 @@ impl Deref for LoadingFunction {
 
 pub struct LoadingTaskConfig {
     delay_full_appearance: bool,
+    background: bool,
 }
 
 impl Default for LoadingTaskConfig {
     fn default() -> Self {
         LoadingTaskConfig {
             delay_full_appearance: true,
+            background: false,
         }
     }
 }
@@ -45,4 +62,73 @@ impl LoadingTaskConfig {
     pub fn get_delay_full_appearance(&self) -> bool {
         self.delay_full_appearance
     }
+    // For work the user doesn't need to wait for, such as refreshing data which is already shown
+    // A small indicator is shown instead of the full screen overlay
+    pub fn background(self, background: bool) -> Self {
+        let mut x = self;
+        x.background = background;
+        x
+    }
+    pub fn get_background(&self) -> bool {
+        self.background
+    }
+}
+
+pub type LoadingTaskId = u32;
+
+// Something which is being waited for
+#[derive(Clone, PartialEq)]
+pub struct PendingTask {
+    pub id: LoadingTaskId,
+    pub delay_full_appearance: bool,
+    pub background: bool,
+    pub text: Option<String>,
+    pub on_cancel: Option<Callback<()>>,
+    pub progress: Option<f64>,
+}
+
+// The tasks which are currently running, in the order they were started
+// Several requests can be in flight at once, so loading only finishes when all of them have
+#[derive(Default)]
+pub struct LoadingTasks {
+    tasks: Vec<PendingTask>,
+}
+
+impl LoadingTasks {
+    pub fn start(&mut self, id: LoadingTaskId, cfg: LoadingTaskConfig) {
+        self.tasks.push(PendingTask {
+            id,
+            delay_full_appearance: cfg.get_delay_full_appearance(),
+            background: cfg.get_background(),
+            text: None,
+            on_cancel: None,
+            progress: None,
+        });
+    }
+
+    pub fn finish(&mut self, id: LoadingTaskId) {
+        self.tasks.retain(|t| t.id != id);
+    }
+
+    pub fn get_mut(&mut self, id: LoadingTaskId) -> Option<&mut PendingTask> {
+        self.tasks.iter_mut().find(|t| t.id == id)
+    }
+
+    // The spinner is only delayed if every task asked for it to be
+    pub fn delay_full_appearance(&self) -> bool {
+        self.foreground().all(|t| t.delay_full_appearance)
+    }
+
+    // Tasks which block the UI until they finish
+    pub fn foreground(&self) -> impl Iterator<Item = &PendingTask> {
+        self.tasks.iter().filter(|t| !t.background)
+    }
+
+    pub fn has_foreground(&self) -> bool {
+        self.foreground().next().is_some()
+    }
+
+    pub fn has_background(&self) -> bool {
+        self.tasks.iter().any(|t| t.background)
+    }
 }
@@ -1,11 +1,13 @@
 use crate::api::error::APIError;
+use crate::api::transport::RequestTask;
 use crate::api::APIClient;
 use crate::components::error::ErrorAlert;
+use crate::components::lockout::LockoutAlert;
 use crate::form_data::GetFormData;
 use crate::loader_task::LoadingFunction;
+use std::time::Duration;
 use web_sys::FormData;
 use yew::prelude::*;
-use yew::services::fetch::FetchTask;
 
 const FIELD_EMAIL: &str = "email";
 
@@ -15,7 +17,8 @@ pub struct ForgotPasswordRoute {
     success: bool,
     error: Option<APIError>,
     email: String,
-    task: Option<FetchTask>,
+    task: Option<RequestTask>,
+    lockout: Option<Duration>,
 }
 
 #[derive(Properties, Clone, PartialEq)]
@@ -27,6 +30,7 @@ pub struct Props {
 pub enum Msg {
     Submit(FormData),
     Response(Result<(), APIError>),
+    LockoutExpired,
 }
 
 impl Component for ForgotPasswordRoute {
@@ -41,6 +45,7 @@ impl Component for ForgotPasswordRoute {
             email: "".to_string(),
             success: false,
             task: None,
+            lockout: None,
         }
     }
 
@@ -48,7 +53,7 @@ impl Component for ForgotPasswordRoute {
         match msg {
             Msg::Submit(fd) => {
                 self.email = fd.get(FIELD_EMAIL).as_string().unwrap();
-                if self.task.is_none() {
+                if self.task.is_none() && self.lockout.is_none() {
                     self.success = false;
                     self.error = None;
                     self.task = Some(self.props.api_client.password_reset_request(
@@ -65,10 +70,16 @@ impl Component for ForgotPasswordRoute {
                         self.success = true;
                     }
                     Err(e) => {
-                        self.error = Some(e);
+                        self.lockout = e.retry_after();
+                        if self.lockout.is_none() {
+                            self.error = Some(e);
+                        }
                     }
                 }
             }
+            Msg::LockoutExpired => {
+                self.lockout = None;
+            }
         }
         true
     }
@@ -108,7 +119,7 @@ impl Component for ForgotPasswordRoute {
 
 impl ForgotPasswordRoute {
     fn form(&self) -> Html {
-        let onsubmit = self.link.on_form_submit(|f| Msg::Submit(f));
+        let onsubmit = self.link.on_form_submit(Msg::Submit);
         html! {
         <>
             <p>{ "Enter your account email address to receive a password reset" }</p>
@@ -124,10 +135,14 @@ impl ForgotPasswordRoute {
                             />
                     </fieldset>
                     <ErrorAlert<APIError> error=&self.error />
+                    <LockoutAlert
+                        retry_after=self.lockout
+                        on_expired=self.link.callback(|_| Msg::LockoutExpired)
+                    />
                     <button
                         class="btn btn-lg btn-primary"
                         type="submit"
-                        disabled=self.task.is_some()>
+                        disabled=self.task.is_some() || self.lockout.is_some()>
                     { "Request Reset" }
                     </button>
                 </fieldset>
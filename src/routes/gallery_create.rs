@@ -1,74 +1,176 @@
 use crate::api::error::APIError;
 use crate::api::gallery::Category;
+use crate::api::transport::RequestTask;
 use crate::api::APIClient;
+use crate::clipboard::{self, PasteWatcher};
+use crate::components::crop_editor::CropEditorComponent;
 use crate::components::enum_selector::EnumSelectorComponent;
 use crate::components::error::ErrorAlert;
-use crate::form_data::GetFormData;
-use crate::loader_task::{BoxedLoadingTask, LoadingFunction, LoadingTaskConfig};
+use crate::components::field_error::{input_classes, FieldError};
+use crate::components::image_preview::{format_bytes, ImagePreviewComponent, ObjectUrl};
+use crate::image_edit::{self, CropRect, Orientation, Resize, JPEG_QUALITY};
+use crate::loader_task::{BoxedLoadingTask, LoadingFunction, LoadingTask, LoadingTaskConfig};
 use crate::routes::{AppRoute, Route, RouteAgentDispatcher};
-use imagesize::{ImageError, ImageSize};
+use enum_iterator::IntoEnumIterator;
+use imagesize::ImageSize;
 use num_rational::Ratio;
+use std::fmt::Formatter;
 use std::rc::Rc;
-use thiserror::Error;
-use web_sys::{File, FormData};
+use std::time::Duration;
+use web_sys::File;
 use yew::prelude::*;
-use yew::services::fetch::FetchTask;
 use yew::services::reader::{FileData, ReaderService, ReaderTask};
+use yew::services::timeout::TimeoutTask;
+use yew::services::{Task, TimeoutService};
 use yew_router::agent::RouteRequest;
 
 const MIN_RECOMMENDED_RESOLUTION: usize = 1920 * 1080;
 const RECOMMENDED_ASPECT: Ratio<usize> = Ratio::new_raw(16, 9);
 const FIELD_DESCRIPTION: &str = "description";
+const FIELD_CATEGORY: &str = "category";
+// Smaller than on the other forms, as there is one for every image
+const ITEM_FORM_CONTROL: &str = "form-control";
+// How long to wait for the settings to stop changing before processing the images again
+const PROCESS_DELAY: Duration = Duration::from_millis(300);
 
-struct Form {
-    description: String,
-    category: Category,
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum UploadStatus {
+    Pending,
+    Uploading,
+    Done,
+    Failed,
+    Skipped,
 }
 
-impl Default for Form {
-    fn default() -> Self {
-        Self {
-            description: Default::default(),
-            category: Category::Staircases,
+impl UploadStatus {
+    fn badge(self) -> (&'static str, &'static str) {
+        match self {
+            UploadStatus::Pending => ("badge badge-secondary", "Waiting"),
+            UploadStatus::Uploading => ("badge badge-primary", "Uploading"),
+            UploadStatus::Done => ("badge badge-success", "Uploaded"),
+            UploadStatus::Failed => ("badge badge-danger", "Failed"),
+            UploadStatus::Skipped => ("badge badge-light", "Skipped"),
         }
     }
 }
 
-#[derive(Debug, Error, Clone)]
-enum Error {
-    #[error("{0}")]
-    APIError(APIError),
-    #[error("{0}")]
-    ImageError(Rc<ImageError>),
+// How wide images can be before they are shrunk, larger than this isn't needed by the public site
+#[derive(Clone, Copy, PartialEq, Debug, IntoEnumIterator)]
+pub enum MaxWidth {
+    FullHd,
+    Qhd,
+    UltraHd,
 }
 
-impl PartialEq for Error {
-    fn eq(&self, other: &Self) -> bool {
-        match &self {
-            Error::APIError(e) => {
-                if let Error::APIError(f) = other {
-                    return e.eq(f);
-                }
-            }
-            Error::ImageError(e) => {
-                if let Error::ImageError(f) = other {
-                    return Rc::ptr_eq(e, f);
-                }
-            }
+impl MaxWidth {
+    fn pixels(self) -> usize {
+        match self {
+            MaxWidth::FullHd => 1920,
+            MaxWidth::Qhd => 2560,
+            MaxWidth::UltraHd => 3840,
         }
-        return false;
     }
 }
 
+impl std::fmt::Display for MaxWidth {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MaxWidth::FullHd => f.write_str("1920px (1080p)"),
+            MaxWidth::Qhd => f.write_str("2560px (1440p)"),
+            MaxWidth::UltraHd => f.write_str("3840px (4K)"),
+        }
+    }
+}
+
+// An image which has been chosen, along with the details it will be uploaded with
+struct UploadItem {
+    id: usize,
+    image: FileData,
+    // Once upright, which for photos from a phone may not be how the pixels are stored
+    size: ImageSize,
+    // None if the browser couldn't create a URL for the image
+    preview: Option<ObjectUrl>,
+    // The part of the image which is uploaded, if it is being cropped to the recommended aspect ratio
+    crop: Option<CropRect>,
+    description: String,
+    category: Category,
+    status: UploadStatus,
+    error: Option<APIError>,
+    // Why the image couldn't be cropped or shrunk before uploading
+    processing_error: Option<String>,
+    // The size of the image once it has been cropped or shrunk, which is worked out before sending
+    output_bytes: Option<usize>,
+    // The cropped or shrunk image, kept so it isn't processed again when it is sent
+    processed: Option<FileData>,
+    progress: Option<f64>,
+    // What the upload is doing, such as retrying after a failure
+    text: Option<String>,
+    on_cancel: Option<Callback<()>>,
+}
+
 pub struct CreateGalleryItemRoute {
     props: Props,
     link: ComponentLink<Self>,
-    error: Option<Error>,
-    task: Option<FetchTask>,
-    form: Form,
+    items: Vec<UploadItem>,
+    next_id: usize,
+    // Files which couldn't be read as an image
+    rejected: Vec<String>,
+    task: Option<RequestTask>,
     loading_task: Option<BoxedLoadingTask>,
-    read_task: Option<ReaderTask>,
-    image: Option<(FileData, ImageSize)>,
+    read_tasks: Vec<ReaderTask>,
+    drag_over: bool,
+    // Large images are shrunk before uploading, unless turned off
+    shrink: bool,
+    max_width: MaxWidth,
+    quality: u8,
+    // Images are processed one at a time, so the page keeps responding in between
+    process_task: Option<TimeoutTask>,
+    _paste_watcher: PasteWatcher,
+}
+
+impl UploadItem {
+    fn editable(&self) -> bool {
+        matches!(self.status, UploadStatus::Pending | UploadStatus::Failed)
+    }
+
+    // For when the crop or settings change, so it is processed again
+    fn clear_output(&mut self) {
+        self.output_bytes = None;
+        self.processed = None;
+        self.processing_error = None;
+    }
+
+    // The size of the image which is uploaded
+    fn output_size(&self, resize: Option<Resize>) -> ImageSize {
+        let (width, height) = match self.crop {
+            Some(c) => (c.width, c.height),
+            None => (self.size.width, self.size.height),
+        };
+        let (width, height) = match resize {
+            Some(r) => r.apply(width, height),
+            None => (width, height),
+        };
+        ImageSize { width, height }
+    }
+
+    // Images are only re-encoded if they need to be changed
+    fn needs_processing(&self, resize: Option<Resize>) -> bool {
+        self.crop.is_some() || self.output_size(resize).width < self.output_size(None).width
+    }
+
+    // The image as it will be uploaded
+    fn output_image(&self, resize: Option<Resize>) -> Result<FileData, String> {
+        let content = if self.needs_processing(resize) {
+            image_edit::process(&self.image.content, self.crop, resize)
+                .map_err(|e| format!("Couldn't process the image: {}", e))?
+        } else {
+            self.image.content.clone()
+        };
+        Ok(FileData {
+            name: self.image.name.clone(),
+            content,
+        })
+    }
 }
 
 #[derive(Properties, Clone, PartialEq)]
@@ -78,11 +180,25 @@ pub struct Props {
 }
 
 pub enum Msg {
-    Submit(FormData),
-    SelectFile(File),
+    SelectFiles(Vec<File>),
+    DragOver(bool),
     FileLoaded(FileData),
-    Response(Result<(), APIError>),
-    CategoryChange(Category),
+    DescriptionChange(usize, String),
+    CategoryChange(usize, Category),
+    CropChange(usize, Option<CropRect>),
+    ToggleShrink,
+    MaxWidthChange(MaxWidth),
+    QualityChange(u8),
+    ProcessNext,
+    UploadAll,
+    Response(usize, Result<(), APIError>),
+    Progress(usize, Option<f64>),
+    TextChange(usize, Option<String>),
+    CancelChange(usize, Option<Callback<()>>),
+    Cancel(usize),
+    Retry(usize),
+    Skip(usize),
+    Unskip(usize),
 }
 
 impl Component for CreateGalleryItemRoute {
@@ -90,69 +206,188 @@ impl Component for CreateGalleryItemRoute {
     type Properties = Props;
 
     fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let paste_watcher = PasteWatcher::new(link.callback(Msg::SelectFiles));
         Self {
             props,
             link,
-            error: None,
+            items: Vec::new(),
+            next_id: 0,
+            rejected: Vec::new(),
             task: None,
-            form: Default::default(),
             loading_task: None,
-            read_task: None,
-            image: None,
+            read_tasks: Vec::new(),
+            drag_over: false,
+            shrink: true,
+            max_width: MaxWidth::Qhd,
+            quality: JPEG_QUALITY,
+            process_task: None,
+            _paste_watcher: paste_watcher,
         }
     }
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match msg {
-            Msg::Submit(fd) => {
-                self.form.description = fd.get(FIELD_DESCRIPTION).as_string().unwrap();
-                self.task = Some(self.props.api_client.gallery_create(
-                    &self.image.as_ref().unwrap().0,
-                    self.form.description.clone(),
-                    &self.form.category,
-                    self.props.loader.clone(),
-                    self.link.callback(Msg::Response),
-                ));
-            }
-            Msg::Response(r) => {
-                self.task = None;
-                match r {
-                    Ok(_) => {
-                        let mut agent = RouteAgentDispatcher::new();
-                        agent.send(RouteRequest::ChangeRoute(Route::from(AppRoute::Gallery)));
-                    }
-                    Err(e) => {
-                        self.error = Some(Error::APIError(e));
-                    }
+            Msg::SelectFiles(files) => {
+                self.drag_over = false;
+                if files.is_empty() {
+                    return false;
                 }
-            }
-            Msg::SelectFile(file) => {
                 self.loading_task = Some((*self.props.loader)(
                     LoadingTaskConfig::default().delay_full_appearance(false),
                 ));
-                let callback = self.link.callback(Msg::FileLoaded);
+                self.rejected.clear();
                 let mut service = ReaderService::new();
-                self.read_task = Some(service.read_file(file, callback).unwrap());
+                for file in files {
+                    let callback = self.link.callback(Msg::FileLoaded);
+                    self.read_tasks
+                        .push(service.read_file(file, callback).unwrap());
+                }
+            }
+            Msg::DragOver(x) => {
+                if self.drag_over == x {
+                    return false;
+                }
+                self.drag_over = x;
             }
             Msg::FileLoaded(data) => {
-                self.read_task = None;
-                self.image = None;
-                self.error = None;
+                self.read_tasks.retain(|t| t.is_active());
                 log::info!("{} {}", data.name, data.content.len());
                 match imagesize::blob_size(&data.content) {
-                    Ok(s) => {
-                        self.image = Some((data, s));
+                    Ok(size) => {
+                        let (width, height) =
+                            Orientation::read(&data.content).apply_to_size(size.width, size.height);
+                        let size = ImageSize { width, height };
+                        // Images are cropped to the recommended aspect ratio unless chosen otherwise
+                        let crop = if has_recommended_aspect(&size) {
+                            None
+                        } else {
+                            CropRect::largest(size.width, size.height, RECOMMENDED_ASPECT)
+                        };
+                        self.items.push(UploadItem {
+                            id: self.next_id,
+                            preview: ObjectUrl::new(&data.content),
+                            image: data,
+                            size,
+                            crop,
+                            description: String::new(),
+                            category: Category::Staircases,
+                            status: UploadStatus::Pending,
+                            error: None,
+                            processing_error: None,
+                            output_bytes: None,
+                            processed: None,
+                            progress: None,
+                            text: None,
+                            on_cancel: None,
+                        });
+                        self.next_id += 1;
                     }
-                    Err(e) => {
-                        self.error = Some(Error::ImageError(Rc::new(e)));
+                    Err(e) => self.rejected.push(format!("{}: {}", data.name, e)),
+                }
+                if self.read_tasks.is_empty() {
+                    self.loading_task = None;
+                }
+                self.process_later();
+            }
+            Msg::DescriptionChange(id, x) => match self.item_mut(id) {
+                Some(i) => i.description = x,
+                None => return false,
+            },
+            Msg::CategoryChange(id, x) => match self.item_mut(id) {
+                Some(i) => i.category = x,
+                None => return false,
+            },
+            Msg::CropChange(id, x) => match self.item_mut(id) {
+                Some(i) if i.editable() => {
+                    i.crop = x;
+                    i.clear_output();
+                    self.process_later();
+                }
+                _ => return false,
+            },
+            Msg::ToggleShrink => {
+                self.shrink = !self.shrink;
+                self.clear_output();
+            }
+            Msg::MaxWidthChange(x) => {
+                self.max_width = x;
+                self.clear_output();
+            }
+            Msg::QualityChange(x) => {
+                self.quality = x;
+                self.clear_output();
+            }
+            Msg::ProcessNext => {
+                self.process_task = None;
+                return self.process_next();
+            }
+            Msg::UploadAll => self.upload_next(),
+            Msg::Response(id, r) => {
+                self.task = None;
+                if let Some(i) = self.item_mut(id) {
+                    i.progress = None;
+                    i.text = None;
+                    i.on_cancel = None;
+                    match r {
+                        Ok(_) => i.status = UploadStatus::Done,
+                        Err(e) => {
+                            i.status = UploadStatus::Failed;
+                            i.error = Some(e);
+                        }
                     }
                 }
-                self.loading_task = None;
+                if self.leave_if_finished() {
+                    return false;
+                }
+                self.upload_next();
             }
-            Msg::CategoryChange(c) => {
-                log::info!("{:?}", c);
-                self.form.category = c;
+            // Updates may arrive after the upload has finished, which are ignored
+            Msg::Progress(id, x) => match self.item_mut(id) {
+                Some(i) if i.status == UploadStatus::Uploading => i.progress = x,
+                _ => return false,
+            },
+            Msg::TextChange(id, x) => match self.item_mut(id) {
+                Some(i) if i.status == UploadStatus::Uploading => i.text = x,
+                _ => return false,
+            },
+            Msg::CancelChange(id, x) => match self.item_mut(id) {
+                Some(i) if i.status == UploadStatus::Uploading => i.on_cancel = x,
+                _ => return false,
+            },
+            Msg::Cancel(id) => {
+                if let Some(c) = self.item_mut(id).and_then(|i| i.on_cancel.clone()) {
+                    c.emit(());
+                }
+                return false;
             }
+            Msg::Retry(id) => {
+                if let Some(i) = self.item_mut(id) {
+                    i.status = UploadStatus::Pending;
+                    i.error = None;
+                    i.processing_error = None;
+                }
+                self.upload_next();
+            }
+            Msg::Skip(id) => match self.item_mut(id) {
+                Some(i) if i.status != UploadStatus::Uploading => {
+                    i.status = UploadStatus::Skipped;
+                    i.error = None;
+                    i.processing_error = None;
+                    if self.leave_if_finished() {
+                        return false;
+                    }
+                }
+                _ => return false,
+            },
+            Msg::Unskip(id) => match self.item_mut(id) {
+                // The settings may have changed while it was skipped
+                Some(i) if i.status == UploadStatus::Skipped => {
+                    i.status = UploadStatus::Pending;
+                    i.clear_output();
+                    self.process_later();
+                }
+                _ => return false,
+            },
         }
         true
     }
@@ -167,58 +402,64 @@ impl Component for CreateGalleryItemRoute {
     }
 
     fn view(&self) -> Html {
-        let onsubmit = self.link.on_form_submit(|f| Msg::Submit(f));
         let onchange = self.link.callback(|v: ChangeData| match v {
-            ChangeData::Files(f) => {
-                let file: File = f.get(0).unwrap();
-                Msg::SelectFile(file)
-            }
+            ChangeData::Files(f) => Msg::SelectFiles(clipboard::files(Some(f))),
             _ => unreachable!(),
         });
-        let oncategory = self.link.callback(|x| Msg::CategoryChange(x));
+        // The default must be prevented for the browser to allow dropping, and not open the file
+        let ondragover = self.link.callback(|e: DragEvent| {
+            e.prevent_default();
+            Msg::DragOver(true)
+        });
+        let ondragleave = self.link.callback(|_: DragEvent| Msg::DragOver(false));
+        let ondrop = self.link.callback(|e: DragEvent| {
+            e.prevent_default();
+            Msg::SelectFiles(clipboard::files(e.data_transfer().and_then(|d| d.files())))
+        });
+        let drop_zone_classes = if self.drag_over { "drag-over" } else { "" };
+        let onupload = self.link.callback(|_: MouseEvent| Msg::UploadAll);
+        let pending = self
+            .items
+            .iter()
+            .filter(|i| i.status == UploadStatus::Pending)
+            .count();
         html! {
             <div class="container">
                 <div class="row">
-                    <div class="col-md-6 offset-md-3 col-xs-12">
-                        <h1 class="mb-3">{ "Upload Image" }</h1>
-                        <form onsubmit=onsubmit>
-                            <fieldset class="form-group">
-                                <label for="file_input">{ "Choose Image" }</label>
-                                <input
-                                    type="file"
-                                    class="form-control-file"
-                                    id="file_input"
-                                    onchange=onchange
-                                    />
-                            </fieldset>
-                            { self.image_info() }
-                            <fieldset class="form-group">
-                                <label>{ "Category" }</label>
-                                <EnumSelectorComponent<Category>
-                                    callback=oncategory
-                                    classes="form-control form-control-lg"
-                                    value=self.form.category.clone()
+                    <div class="col-md-8 offset-md-2 col-xs-12">
+                        <h1 class="mb-3">{ "Upload Images" }</h1>
+                        <div
+                            id="drop-zone"
+                            class=drop_zone_classes
+                            ondragover=ondragover
+                            ondragleave=ondragleave
+                            ondrop=ondrop>
+                            <p>{ "Drag images here, or paste them from the clipboard" }</p>
+                            <label for="file_input" class="mr-2">{ "Or choose images:" }</label>
+                            <input
+                                type="file"
+                                class="form-control-file"
+                                id="file_input"
+                                accept="image/*"
+                                multiple=true
+                                onchange=onchange
                                 />
-                            </fieldset>
-                            <fieldset class="form-group">
-                                <label for="description_textarea">{ "Image Description" }</label>
-                                <textarea
-                                    class="form-control form-control-lg"
-                                    id="description_textarea"
-                                    rows="4"
-                                    maxlength="4096"
-                                    name=FIELD_DESCRIPTION
-                                    value=&self.form.description
-                                    />
-                            </fieldset>
-                            <ErrorAlert<Error> error=&self.error />
-                            <button
-                                class="btn btn-lg btn-primary"
-                                type="submit"
-                                disabled=self.task.is_some() || self.image.is_none()>
-                                { "Upload" }
-                            </button>
-                        </form>
+                        </div>
+                        { self.rejected_view() }
+                        { self.shrink_view() }
+                        { self.items.iter().map(|i| self.item_view(i)).collect::<Html>() }
+                        <button
+                            class="btn btn-lg btn-primary mb-3"
+                            type="button"
+                            onclick=onupload
+                            disabled=self.task.is_some() || pending == 0>
+                            {
+                                match pending {
+                                    1 => "Upload 1 image".to_string(),
+                                    n => format!("Upload {} images", n),
+                                }
+                            }
+                        </button>
                     </div>
                 </div>
             </div>
@@ -227,36 +468,455 @@ impl Component for CreateGalleryItemRoute {
 }
 
 impl CreateGalleryItemRoute {
-    fn image_info(&self) -> Html {
-        match &self.image {
-            None => return html! {},
-            Some((_, size)) => {
-                let resolution = size.height * size.width;
-                let aspect_ratio = Ratio::new(size.width, size.height);
-                let mut warnings = Vec::new();
-                if resolution < MIN_RECOMMENDED_RESOLUTION {
-                    warnings.push("Resolution is lower than the recommended minimum (1080p)")
+    fn item_mut(&mut self, id: usize) -> Option<&mut UploadItem> {
+        self.items.iter_mut().find(|i| i.id == id)
+    }
+
+    fn resize(&self) -> Option<Resize> {
+        if self.shrink {
+            Some(Resize {
+                max_width: self.max_width.pixels(),
+                quality: self.quality,
+            })
+        } else {
+            None
+        }
+    }
+
+    // Images which haven't been sent yet will be processed again with the new settings
+    fn clear_output(&mut self) {
+        for i in self.items.iter_mut().filter(|i| i.editable()) {
+            i.clear_output();
+        }
+        self.process_later();
+    }
+
+    // Restarts the wait when called again, so changes such as dragging the quality slider are only processed once
+    fn process_later(&mut self) {
+        self.process_task = Some(TimeoutService::spawn(
+            PROCESS_DELAY,
+            self.link.callback(|_| Msg::ProcessNext),
+        ));
+    }
+
+    // Works out the size of the next image waiting to be sent, returning whether one was processed
+    fn process_next(&mut self) -> bool {
+        let resize = self.resize();
+        let item = match self
+            .items
+            .iter_mut()
+            .find(|i| i.editable() && i.output_bytes.is_none() && i.processing_error.is_none())
+        {
+            Some(i) => i,
+            None => return false,
+        };
+        if item.needs_processing(resize) {
+            match item.output_image(resize) {
+                Ok(image) => {
+                    item.output_bytes = Some(image.content.len());
+                    item.processed = Some(image);
                 }
-                if aspect_ratio != RECOMMENDED_ASPECT {
-                    warnings.push("Different aspect ratio to the recommended size (16:9)")
+                Err(e) => item.processing_error = Some(e),
+            }
+        } else {
+            item.output_bytes = Some(item.image.content.len());
+        }
+        // The next image waits for this one to be shown
+        self.process_task = Some(TimeoutService::spawn(
+            Duration::from_millis(0),
+            self.link.callback(|_| Msg::ProcessNext),
+        ));
+        true
+    }
+
+    // Once every image has been uploaded or skipped there is nothing left to do here
+    fn leave_if_finished(&self) -> bool {
+        let finished = self
+            .items
+            .iter()
+            .all(|i| matches!(i.status, UploadStatus::Done | UploadStatus::Skipped));
+        if !finished || !self.items.iter().any(|i| i.status == UploadStatus::Done) {
+            return false;
+        }
+        let mut agent = RouteAgentDispatcher::new();
+        agent.send(RouteRequest::ChangeRoute(Route::from(AppRoute::Gallery)));
+        true
+    }
+
+    // Images are uploaded one at a time, in the order they were chosen
+    fn upload_next(&mut self) {
+        if self.task.is_some() {
+            return;
+        }
+        let resize = self.resize();
+        let item = match self
+            .items
+            .iter_mut()
+            .find(|i| i.status == UploadStatus::Pending)
+        {
+            Some(i) => i,
+            None => return,
+        };
+        // The image may not have been processed yet, if it was sent straight after changing the settings
+        let processed = item.processed.take().map(Ok);
+        let image = match processed.unwrap_or_else(|| item.output_image(resize)) {
+            Ok(x) => x,
+            Err(e) => {
+                item.status = UploadStatus::Failed;
+                item.processing_error = Some(e);
+                return self.upload_next();
+            }
+        };
+        item.status = UploadStatus::Uploading;
+        item.error = None;
+        item.processing_error = None;
+        item.output_bytes = Some(image.content.len());
+        let id = item.id;
+        // Progress is shown on the item, so the rest of the queue stays visible
+        let link = self.link.clone();
+        let loader = LoadingFunction(Rc::new(move |_| {
+            Box::new(ItemLoadingTask {
+                id,
+                link: link.clone(),
+            })
+        }));
+        self.task = Some(self.props.api_client.gallery_create(
+            &image,
+            item.description.clone(),
+            &item.category,
+            loader,
+            self.link.callback(move |r| Msg::Response(id, r)),
+        ));
+    }
+
+    fn rejected_view(&self) -> Html {
+        if self.rejected.is_empty() {
+            return html! {};
+        }
+        html! {
+            <div class="alert alert-danger" role="alert">
+                {"These files couldn't be read as images:"}
+                <ul class="mb-0">
+                    { self.rejected.iter().map(|x| html! {<li>{x}</li>}).collect::<Html>() }
+                </ul>
+            </div>
+        }
+    }
+
+    fn shrink_view(&self) -> Html {
+        if self.items.is_empty() {
+            return html! {};
+        }
+        let ontoggle = self.link.callback(|_: MouseEvent| Msg::ToggleShrink);
+        let onmaxwidth = self.link.callback(Msg::MaxWidthChange);
+        let onquality = self
+            .link
+            .callback(|e: InputData| Msg::QualityChange(e.value.parse().unwrap_or(JPEG_QUALITY)));
+        html! {
+            <div class="card mb-3">
+                <div class="card-body">
+                    <fieldset class="form-group form-check">
+                        <input
+                            class="form-check-input"
+                            type="checkbox"
+                            id="shrink_checkbox"
+                            checked=self.shrink
+                            onclick=ontoggle
+                            />
+                        <label class="form-check-label" for="shrink_checkbox">
+                            { "Shrink large images before uploading" }
+                        </label>
+                    </fieldset>
+                    <fieldset class="form-row mb-0" disabled=!self.shrink>
+                        <div class="form-group col-md-6 mb-0">
+                            <label>{ "Maximum width" }</label>
+                            <EnumSelectorComponent<MaxWidth>
+                                callback=onmaxwidth
+                                classes=ITEM_FORM_CONTROL
+                                value=self.max_width
+                            />
+                        </div>
+                        <div class="form-group col-md-6 mb-0">
+                            <label for="quality_input">{ format!("JPEG quality: {}", self.quality) }</label>
+                            <input
+                                type="range"
+                                class="custom-range"
+                                id="quality_input"
+                                min="50"
+                                max="100"
+                                value=self.quality.to_string()
+                                oninput=onquality
+                                />
+                        </div>
+                    </fieldset>
+                </div>
+            </div>
+        }
+    }
+
+    // The size of the image that is sent, next to the size it was chosen at
+    fn output_view(&self, item: &UploadItem) -> Html {
+        let resize = self.resize();
+        let size = item.output_size(resize);
+        let verb = if item.editable() {
+            "Will be sent"
+        } else {
+            "Sent"
+        };
+        let text = match item.output_bytes {
+            Some(after) => format!(
+                "{} as {} × {}, {} before and {} after processing",
+                verb,
+                size.width,
+                size.height,
+                format_bytes(item.image.content.len()),
+                format_bytes(after)
+            ),
+            None if item.processing_error.is_none() && item.needs_processing(resize) => format!(
+                "{} as {} × {}, working out the size after processing…",
+                verb, size.width, size.height
+            ),
+            None => return html! {},
+        };
+        html! {
+            <p class="small text-muted">{text}</p>
+        }
+    }
+
+    fn item_view(&self, item: &UploadItem) -> Html {
+        let id = item.id;
+        let editable = item.editable();
+        let (badge_classes, status) = item.status.badge();
+        let oncategory = self.link.callback(move |x| Msg::CategoryChange(id, x));
+        let ondescription = self
+            .link
+            .callback(move |e: InputData| Msg::DescriptionChange(id, e.value));
+        let description_id = format!("description_{}", id);
+        html! {
+            <div class="card mb-3">
+                <div class="card-body">
+                    <h5 class="card-title">
+                        {&item.image.name}{" "}
+                        <span class=badge_classes>{status}</span>
+                    </h5>
+                    { self.preview_view(item) }
+                    { self.output_view(item) }
+                    { image_warnings(&item.output_size(self.resize())) }
+                    <fieldset disabled=!editable>
+                        <fieldset class="form-group">
+                            <label>{ "Category" }</label>
+                            <EnumSelectorComponent<Category>
+                                callback=oncategory
+                                classes=input_classes(ITEM_FORM_CONTROL, &item.error, FIELD_CATEGORY)
+                                value=item.category.clone()
+                            />
+                            <FieldError error=&item.error field=FIELD_CATEGORY />
+                        </fieldset>
+                        <fieldset class="form-group">
+                            <label for=description_id.clone()>{ "Image Description" }</label>
+                            <textarea
+                                class=input_classes(ITEM_FORM_CONTROL, &item.error, FIELD_DESCRIPTION)
+                                id=description_id
+                                rows="3"
+                                maxlength="4096"
+                                value=&item.description
+                                oninput=ondescription
+                                />
+                            <FieldError error=&item.error field=FIELD_DESCRIPTION />
+                        </fieldset>
+                    </fieldset>
+                    <ErrorAlert<APIError> error=&item.error />
+                    {
+                        match &item.processing_error {
+                            Some(e) => html! {
+                                <div class="alert alert-danger" role="alert">{e}</div>
+                            },
+                            None => html! {},
+                        }
+                    }
+                    { self.item_actions(item) }
+                </div>
+            </div>
+        }
+    }
+
+    fn preview_view(&self, item: &UploadItem) -> Html {
+        let url = match &item.preview {
+            Some(x) => x.as_str().to_string(),
+            None => return html! {},
+        };
+        let id = item.id;
+        let editable = item.editable();
+        match item.crop {
+            Some(crop) => {
+                let onchange = self.link.callback(move |x| Msg::CropChange(id, Some(x)));
+                let onkeep = self
+                    .link
+                    .callback(move |_: MouseEvent| Msg::CropChange(id, None));
+                html! {
+                    <>
+                        <CropEditorComponent
+                            url=url
+                            size=item.size
+                            aspect=RECOMMENDED_ASPECT
+                            crop=crop
+                            on_change=onchange
+                            disabled=!editable
+                            />
+                        <button
+                            type="button"
+                            class="btn btn-sm btn-outline-secondary mb-3"
+                            onclick=onkeep
+                            disabled=!editable>
+                            {"Keep the original aspect ratio"}
+                        </button>
+                    </>
                 }
-                if warnings.len() > 0 {
-                    return html! {
-                        <div class="alert alert-warning" role="alert">
-                            {"Image Warnings:"}
-                            <ul>
-                                {warnings
-                                .iter()
-                                .map(|x| html! {<li>{x}</li>})
-                                .collect::<Html>()
+            }
+            None => {
+                let size = item.size;
+                let largest = CropRect::largest(size.width, size.height, RECOMMENDED_ASPECT);
+                let oncrop = self
+                    .link
+                    .callback(move |_: MouseEvent| Msg::CropChange(id, largest));
+                html! {
+                    <>
+                        <ImagePreviewComponent
+                            url=url
+                            size=item.size
+                            bytes=item.image.content.len()
+                            frame_aspect=RECOMMENDED_ASPECT
+                            />
+                        {
+                            // Images smaller than 16 × 9 can't be cropped to it
+                            if has_recommended_aspect(&item.size) || largest.is_none() {
+                                html! {}
+                            } else {
+                                html! {
+                                    <button
+                                        type="button"
+                                        class="btn btn-sm btn-outline-secondary mb-3"
+                                        onclick=oncrop
+                                        disabled=!editable>
+                                        {"Crop to 16:9"}
+                                    </button>
                                 }
-                            </ul>
+                            }
+                        }
+                    </>
+                }
+            }
+        }
+    }
+
+    fn item_actions(&self, item: &UploadItem) -> Html {
+        let id = item.id;
+        let onretry = self.link.callback(move |_: MouseEvent| Msg::Retry(id));
+        let onskip = self.link.callback(move |_: MouseEvent| Msg::Skip(id));
+        let onunskip = self.link.callback(move |_: MouseEvent| Msg::Unskip(id));
+        let oncancel = self.link.callback(move |_: MouseEvent| Msg::Cancel(id));
+        match item.status {
+            UploadStatus::Uploading => {
+                let percent = (item.progress.unwrap_or(0.0) * 100.0) as u32;
+                html! {
+                    <>
+                        <div class="progress mb-2">
+                            <div
+                                class="progress-bar"
+                                role="progressbar"
+                                style=format!("width: {}%", percent)
+                                aria-valuenow=percent
+                                aria-valuemin="0"
+                                aria-valuemax="100">
+                            </div>
                         </div>
-                    };
-                } else {
-                    return html! {};
+                        {
+                            match &item.text {
+                                Some(x) => html! {<p class="small text-muted mb-2">{x}</p>},
+                                None => html! {},
+                            }
+                        }
+                        {
+                            if item.on_cancel.is_some() {
+                                html! {
+                                    <button type="button" class="btn btn-sm btn-secondary" onclick=oncancel>
+                                        {"Cancel"}
+                                    </button>
+                                }
+                            } else {
+                                html! {}
+                            }
+                        }
+                    </>
                 }
             }
-        };
+            UploadStatus::Failed => html! {
+                <>
+                    <button type="button" class="btn btn-sm btn-primary mr-2" onclick=onretry>
+                        {"Retry"}
+                    </button>
+                    <button type="button" class="btn btn-sm btn-secondary" onclick=onskip>
+                        {"Skip"}
+                    </button>
+                </>
+            },
+            UploadStatus::Pending => html! {
+                <button type="button" class="btn btn-sm btn-secondary" onclick=onskip>
+                    {"Skip"}
+                </button>
+            },
+            UploadStatus::Skipped => html! {
+                <button type="button" class="btn btn-sm btn-secondary" onclick=onunskip>
+                    {"Undo skip"}
+                </button>
+            },
+            UploadStatus::Done => html! {},
+        }
+    }
+}
+
+fn has_recommended_aspect(size: &ImageSize) -> bool {
+    size.height > 0 && Ratio::new(size.width, size.height) == RECOMMENDED_ASPECT
+}
+
+fn image_warnings(size: &ImageSize) -> Html {
+    let resolution = size.height * size.width;
+    let mut warnings = Vec::new();
+    if resolution < MIN_RECOMMENDED_RESOLUTION {
+        warnings.push("Resolution is lower than the recommended minimum (1080p)")
+    }
+    if !has_recommended_aspect(size) {
+        warnings.push("Different aspect ratio to the recommended size (16:9)")
+    }
+    if warnings.is_empty() {
+        return html! {};
+    }
+    html! {
+        <div class="alert alert-warning" role="alert">
+            {"Image Warnings:"}
+            <ul class="mb-0">
+                { warnings.iter().map(|x| html! {<li>{x}</li>}).collect::<Html>() }
+            </ul>
+        </div>
+    }
+}
+
+// Shows the progress of an upload on its item
+struct ItemLoadingTask {
+    id: usize,
+    link: ComponentLink<CreateGalleryItemRoute>,
+}
+
+impl LoadingTask for ItemLoadingTask {
+    fn set_text(&self, x: Option<String>) {
+        self.link.send_message(Msg::TextChange(self.id, x));
+    }
+
+    fn set_on_cancel(&self, x: Option<Callback<()>>) {
+        self.link.send_message(Msg::CancelChange(self.id, x));
+    }
+
+    fn set_progress(&self, x: Option<f64>) {
+        self.link.send_message(Msg::Progress(self.id, x));
     }
 }
@@ -1,17 +1,20 @@
 use crate::api::error::APIError;
 use crate::api::gallery::{Category, GalleryFileResponse, GalleryItemResponse};
+use crate::api::transport::RequestTask;
 use crate::api::APIClient;
+use crate::components::conflict::{ConflictComponent, ConflictField};
 use crate::components::enum_selector::EnumSelectorComponent;
 use crate::components::error::ErrorAlert;
+use crate::components::field_error::{input_classes, FieldError, FORM_CONTROL};
 use crate::form_data::GetFormData;
 use crate::loader_task::LoadingFunction;
 use crate::routes::{AppRoute, Route, RouteAgentDispatcher};
 use web_sys::FormData;
 use yew::prelude::*;
-use yew::services::fetch::FetchTask;
 use yew_router::agent::RouteRequest;
 
 const FIELD_DESCRIPTION: &str = "description";
+const FIELD_CATEGORY: &str = "category";
 
 struct Form {
     description: String,
@@ -30,13 +33,15 @@ impl Default for Form {
 pub struct EditGalleryItemRoute {
     props: Props,
     link: ComponentLink<Self>,
-    load_task: Option<FetchTask>,
-    task: Option<FetchTask>,
+    load_task: Option<RequestTask>,
+    task: Option<RequestTask>,
     load_error: Option<APIError>,
     edit_error: Option<APIError>,
     delete_error: Option<APIError>,
     form: Form,
     image: Option<GalleryFileResponse>,
+    version: Option<u32>,
+    conflict: Option<GalleryItemResponse>, // The current version, if someone else changed it
 }
 
 #[derive(Properties, Clone, PartialEq)]
@@ -50,6 +55,9 @@ pub enum Msg {
     LoadResponse(Result<GalleryItemResponse, APIError>),
     Submit(FormData),
     EditResponse(Result<(), APIError>),
+    ConflictResponse(Result<GalleryItemResponse, APIError>),
+    Overwrite,
+    Reload,
     ConfirmDelete,
     DeleteResponse(Result<(), APIError>),
     CategoryChange(Category),
@@ -75,6 +83,8 @@ impl Component for EditGalleryItemRoute {
             delete_error: None,
             form: Default::default(),
             image: None,
+            version: None,
+            conflict: None,
         }
     }
 
@@ -82,26 +92,18 @@ impl Component for EditGalleryItemRoute {
         match msg {
             Msg::Submit(fd) => {
                 self.form.description = fd.get(FIELD_DESCRIPTION).as_string().unwrap();
-                if self.load_task.is_none() && self.task.is_none() {
-                    self.edit_error = None;
-                    self.task = Some(self.props.api_client.gallery_update(
-                        self.props.item_id,
-                        self.form.description.clone(),
-                        self.form.category.clone(),
-                        None,
-                        false,
-                        self.props.on_loading.clone(),
-                        self.link.callback(Msg::EditResponse),
-                    ));
+                if self.load_task.is_none() && self.task.is_none() && self.conflict.is_none() {
+                    self.send_update();
                 }
             }
             Msg::LoadResponse(r) => {
                 self.load_task = None;
                 match r {
                     Ok(x) => {
-                        self.image = x.best_matching_width(800).map(|x| x.clone());
+                        self.image = x.best_matching_width(800).cloned();
                         self.form.description = x.description;
                         self.form.category = x.category;
+                        self.version = x.version;
                     }
                     Err(e) => {
                         self.load_error = Some(e);
@@ -111,15 +113,44 @@ impl Component for EditGalleryItemRoute {
             Msg::EditResponse(r) => {
                 self.task = None;
                 match r {
-                    Ok(_) => {
+                    // A queued change is shown in the header, and will be sent once back online
+                    Ok(_) | Err(APIError::Queued) => {
                         let mut agent = RouteAgentDispatcher::new();
                         agent.send(RouteRequest::ChangeRoute(Route::from(AppRoute::Gallery)));
                     }
+                    Err(APIError::Conflict(_)) => {
+                        self.task = Some(self.props.api_client.gallery_get(
+                            self.props.item_id,
+                            self.props.on_loading.clone(),
+                            self.link.callback(Msg::ConflictResponse),
+                        ));
+                    }
                     Err(e) => {
                         self.edit_error = Some(e);
                     }
                 }
             }
+            Msg::ConflictResponse(r) => {
+                self.task = None;
+                match r {
+                    Ok(x) => self.conflict = Some(x),
+                    Err(e) => self.edit_error = Some(e),
+                }
+            }
+            Msg::Overwrite => {
+                if let Some(x) = self.conflict.take() {
+                    self.version = x.version;
+                    self.send_update();
+                }
+            }
+            Msg::Reload => {
+                if let Some(x) = self.conflict.take() {
+                    self.image = x.best_matching_width(800).cloned();
+                    self.form.description = x.description;
+                    self.form.category = x.category;
+                    self.version = x.version;
+                }
+            }
             Msg::ConfirmDelete => {
                 self.delete_error = None;
                 self.task = Some(self.props.api_client.gallery_delete(
@@ -181,9 +212,48 @@ impl Component for EditGalleryItemRoute {
 }
 
 impl EditGalleryItemRoute {
+    fn send_update(&mut self) {
+        self.edit_error = None;
+        self.task = Some(self.props.api_client.gallery_update(
+            self.props.item_id,
+            self.form.description.clone(),
+            self.form.category.clone(),
+            self.version,
+            self.props.on_loading.clone(),
+            self.link.callback(Msg::EditResponse),
+        ));
+    }
+
+    fn conflict(&self) -> Html {
+        match &self.conflict {
+            None => html! {},
+            Some(x) => {
+                let fields = vec![
+                    ConflictField {
+                        label: "Category",
+                        server: x.category.to_string(),
+                        local: self.form.category.to_string(),
+                    },
+                    ConflictField {
+                        label: "Description",
+                        server: x.description.clone(),
+                        local: self.form.description.clone(),
+                    },
+                ];
+                html! {
+                    <ConflictComponent
+                        fields=fields
+                        on_overwrite=self.link.callback(|_| Msg::Overwrite)
+                        on_reload=self.link.callback(|_| Msg::Reload)
+                    />
+                }
+            }
+        }
+    }
+
     fn form(&self) -> Html {
-        let oncategory = self.link.callback(|x| Msg::CategoryChange(x));
-        let onsubmit = self.link.on_form_submit(|f| Msg::Submit(f));
+        let oncategory = self.link.callback(Msg::CategoryChange);
+        let onsubmit = self.link.on_form_submit(Msg::Submit);
         html! {
         <>
             <h1 class="mb-3">{ "Edit image" }</h1>
@@ -198,26 +268,29 @@ impl EditGalleryItemRoute {
                     <label>{ "Category" }</label>
                     <EnumSelectorComponent<Category>
                         callback=oncategory
-                        classes="form-control form-control-lg"
+                        classes=input_classes(FORM_CONTROL, &self.edit_error, FIELD_CATEGORY)
                         value=self.form.category.clone()
                     />
+                    <FieldError error=&self.edit_error field=FIELD_CATEGORY />
                 </fieldset>
                 <fieldset class="form-group">
                     <label for="description_textarea">{ "Image Description" }</label>
                     <textarea
-                        class="form-control form-control-lg"
+                        class=input_classes(FORM_CONTROL, &self.edit_error, FIELD_DESCRIPTION)
                         id="description_textarea"
                         rows="4"
                         maxlength="4096"
                         name=FIELD_DESCRIPTION
                         value=&self.form.description
                         />
+                    <FieldError error=&self.edit_error field=FIELD_DESCRIPTION />
                 </fieldset>
                 <ErrorAlert<APIError> error=&self.edit_error />
+                { self.conflict() }
                 <button
                     class="btn btn-lg btn-primary"
                     type="submit"
-                    disabled=self.task.is_some()
+                    disabled=self.task.is_some() || self.conflict.is_some()
                     > { "Update" }
                 </button>
                 <hr/>
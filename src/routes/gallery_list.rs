@@ -1,5 +1,6 @@
 use crate::api::error::APIError;
 use crate::api::gallery::{Category, GalleryItemResponse, GalleryListResponse};
+use crate::api::transport::RequestTask;
 use crate::api::APIClient;
 use crate::bindings::sortable::{OnEndEvent, Sortable, SortableOptions};
 use crate::components::error::ErrorAlert;
@@ -9,13 +10,13 @@ use enum_iterator::IntoEnumIterator;
 use std::collections::HashMap;
 use wasm_bindgen::closure::Closure;
 use yew::prelude::*;
-use yew::services::fetch::FetchTask;
 
 pub struct ListGalleryRoute {
     props: Props,
     link: ComponentLink<Self>,
-    task: Option<FetchTask>,
+    task: Option<RequestTask>,
     error: Option<APIError>,
+    move_error: Option<APIError>, // Shown above the list, which is reloaded from the API
     results: Option<GalleryListResponse>,
     on_ends: HashMap<Category, Closure<dyn FnMut(OnEndEvent)>>,
     do_refresh: bool, // Hack to force yew to rerender the lists
@@ -32,6 +33,7 @@ pub enum Msg {
     PositionChange(Category, u32, u32),
     PositionChangeResponse(Result<(), APIError>),
     CompleteRefresh,
+    Refreshed,
 }
 
 impl Component for ListGalleryRoute {
@@ -59,6 +61,7 @@ impl Component for ListGalleryRoute {
             link,
             task: None,
             error: None,
+            move_error: None,
             results: None,
             on_ends,
             do_refresh: false,
@@ -68,7 +71,8 @@ impl Component for ListGalleryRoute {
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match msg {
             Msg::Response(r) => {
-                self.task = None;
+                // Cached data may still be checked in the background, which shouldn't be cancelled
+                self.task = self.task.take().filter(|t| t.is_active());
                 match r {
                     Ok(r) => {
                         self.error = None;
@@ -83,36 +87,43 @@ impl Component for ListGalleryRoute {
             Msg::PositionChange(category, old, new) => {
                 let category_list = self.results.as_mut().unwrap().get_mut(&category).unwrap();
                 let item = category_list.get(old as usize).unwrap().clone();
-                let (move_to_front, move_after_id) = if new == 0 {
-                    (true, None)
+                let move_after_id = if new == 0 {
+                    None
                 } else {
                     let prev_index = if new > old { new } else { new - 1 };
-                    let id = category_list.get(prev_index as usize).unwrap().id;
-                    (false, Some(id))
+                    Some(category_list.get(prev_index as usize).unwrap().id)
                 };
                 // Do the swap in local storage
                 let removed = category_list.remove(old as usize);
                 category_list.insert(new as usize, removed);
                 // Send the swap details to the server
-                self.task = Some(self.props.api_client.gallery_update(
-                    item.id,
-                    item.description.clone(),
-                    item.category.clone(),
+                self.move_error = None;
+                self.task = Some(self.props.api_client.gallery_move(
+                    &item,
                     move_after_id,
-                    move_to_front,
                     self.props.on_loading.clone(),
-                    self.link.callback(|x| Msg::PositionChangeResponse(x)),
+                    self.link.callback(Msg::PositionChangeResponse),
                 ));
                 self.do_refresh = true;
                 self.link.send_message(Msg::CompleteRefresh);
             }
-            Msg::PositionChangeResponse(_) => {
-                self.refresh();
+            // The local order is kept until the move is sent
+            Msg::PositionChangeResponse(Err(APIError::Queued)) => return false,
+            // Someone else may have changed the item, so the list is reloaded to show the order
+            // the API has, rather than the local one
+            Msg::PositionChangeResponse(r) => {
+                self.move_error = r.err();
+                self.refresh(true);
                 return false; // Defer until the response from refresh
             }
             Msg::CompleteRefresh => {
                 self.do_refresh = false;
             }
+            // The list shown came from the cache, and has since changed
+            Msg::Refreshed => {
+                self.refresh(true);
+                return false;
+            }
         };
         true
     }
@@ -133,6 +144,7 @@ impl Component for ListGalleryRoute {
             <RouterAnchor route=AppRoute::GalleryCreate classes="btn btn-secondary mb-3">
                 { "Upload new image" }
             </RouterAnchor>
+            <ErrorAlert<APIError> classes="mb-3" error=&self.move_error />
             {
                 if self.error.is_some() {
                     html!{<ErrorAlert<APIError> classes="mt-3" error=&self.error />}
@@ -148,30 +160,34 @@ impl Component for ListGalleryRoute {
 
     fn rendered(&mut self, first_render: bool) {
         if first_render {
-            self.refresh();
+            self.refresh(false);
         }
         let window = web_sys::window().expect("no global `window` exists");
         let document = window.document().expect("should have a document on window");
 
         for i in Category::into_enum_iter() {
             let e = document.get_element_by_id(category_to_id(&i).as_str());
-            match e {
-                Some(e) => {
-                    let options = SortableOptions::new();
-                    options.set_on_end(&self.on_ends[&i]);
-                    Sortable::create(&e, options);
-                }
-                None => {}
+            if let Some(e) = e {
+                let options = SortableOptions::new();
+                options.set_on_end(&self.on_ends[&i]);
+                Sortable::create(&e, options);
             }
         }
     }
 }
 
 impl ListGalleryRoute {
-    fn refresh(&mut self) {
+    // The list is already shown after a reorder, so it can be refreshed in the background
+    fn refresh(&mut self, background: bool) {
+        let loader = if background {
+            self.props.on_loading.background()
+        } else {
+            self.props.on_loading.clone()
+        };
         self.task = Some(self.props.api_client.gallery_list(
-            self.props.on_loading.clone(),
-            self.link.callback(|x| Msg::Response(x)),
+            loader,
+            self.link.callback(|_| Msg::Refreshed),
+            self.link.callback(Msg::Response),
         ));
     }
 
@@ -182,17 +198,17 @@ impl ListGalleryRoute {
             .as_ref()
             .and_then(|x| x.get(&category))
             .unwrap_or(&default);
-        if items.len() > 0 {
-            return html! {
+        if !items.is_empty() {
+            html! {
                 <div class="row gallery-list-category">
                     <h4 class="col-12 p-2 mb-3 bg-light text-dark">{category.to_string()}</h4>
                     <div class="col card-deck" id={category_to_id(&category)}>
                         {items.iter().map(|i| self.render_item(i)).collect::<Html>()}
                     </div>
                 </div>
-            };
+            }
         } else {
-            return html! {};
+            html! {}
         }
     }
 
@@ -1,41 +1,47 @@
 use crate::api::error::APIError;
 use crate::api::session::LoginResponse;
+use crate::api::transport::RequestTask;
 use crate::api::APIClient;
 use crate::components::error::ErrorAlert;
+use crate::components::lockout::LockoutAlert;
 use crate::form_data::GetFormData;
 use crate::loader_task::LoadingFunction;
 use crate::routes::{AppRoute, RouterAnchor};
+use std::time::Duration;
 use web_sys::FormData;
 use yew::prelude::*;
-use yew::services::fetch::FetchTask;
 
 const FIELD_EMAIL: &str = "email";
 const FIELD_PASSWORD: &str = "password";
+const FIELD_REMEMBER: &str = "remember";
 
 #[derive(Default)]
 struct Form {
     email: String,
     password: String,
+    remember: bool,
 }
 
 pub struct LoginRoute {
     props: Props,
     link: ComponentLink<Self>,
     form: Form,
-    task: Option<FetchTask>,
+    task: Option<RequestTask>,
     error: Option<APIError>,
+    lockout: Option<Duration>,
 }
 
 #[derive(Properties, Clone, PartialEq)]
 pub struct Props {
     pub api_client: APIClient,
-    pub on_login: Callback<LoginResponse>,
+    pub on_login: Callback<(LoginResponse, bool)>, // The login, and whether to remember it
     pub on_loading: LoadingFunction,
 }
 
 pub enum Msg {
     Submit(FormData),
     Response(Result<LoginResponse, APIError>),
+    LockoutExpired,
 }
 
 impl Component for LoginRoute {
@@ -49,6 +55,7 @@ impl Component for LoginRoute {
             form: Default::default(),
             task: None,
             error: None,
+            lockout: None,
         }
     }
 
@@ -57,7 +64,8 @@ impl Component for LoginRoute {
             Msg::Submit(fd) => {
                 self.form.email = fd.get(FIELD_EMAIL).as_string().unwrap();
                 self.form.password = fd.get(FIELD_PASSWORD).as_string().unwrap();
-                if self.task.is_none() {
+                self.form.remember = fd.has(FIELD_REMEMBER);
+                if self.task.is_none() && self.lockout.is_none() {
                     self.error = None;
                     self.task = Some(self.props.api_client.session_login(
                         self.form.email.clone(),
@@ -71,13 +79,19 @@ impl Component for LoginRoute {
                 self.task = None;
                 match r {
                     Ok(s) => {
-                        self.props.on_login.emit(s);
+                        self.props.on_login.emit((s, self.form.remember));
                     }
                     Err(e) => {
-                        self.error = Some(e);
+                        self.lockout = e.retry_after();
+                        if self.lockout.is_none() {
+                            self.error = Some(e);
+                        }
                     }
                 }
             }
+            Msg::LockoutExpired => {
+                self.lockout = None;
+            }
         }
         true
     }
@@ -92,7 +106,7 @@ impl Component for LoginRoute {
     }
 
     fn view(&self) -> Html {
-        let onsubmit = self.link.on_form_submit(|f| Msg::Submit(f));
+        let onsubmit = self.link.on_form_submit(Msg::Submit);
         html! {
             <div class="container">
                 <div class="row">
@@ -118,16 +132,32 @@ impl Component for LoginRoute {
                                         value=&self.form.password
                                         />
                                 </fieldset>
+                                <fieldset class="form-group form-check">
+                                    <input
+                                        class="form-check-input"
+                                        type="checkbox"
+                                        id="remember_checkbox"
+                                        name=FIELD_REMEMBER
+                                        checked=self.form.remember
+                                        />
+                                    <label class="form-check-label" for="remember_checkbox">
+                                        { "Remember me" }
+                                    </label>
+                                </fieldset>
                                 <p class="text-xs-center">
                                     <RouterAnchor route=AppRoute::ForgotPassword>
                                         { "Forgot Password?" }
                                     </RouterAnchor>
                                 </p>
                                 <ErrorAlert<APIError> error=&self.error />
+                                <LockoutAlert
+                                    retry_after=self.lockout
+                                    on_expired=self.link.callback(|_| Msg::LockoutExpired)
+                                />
                                 <button
                                     class="btn btn-lg btn-primary"
                                     type="submit"
-                                    disabled=self.task.is_some()>
+                                    disabled=self.task.is_some() || self.lockout.is_some()>
                                     { "Sign in" }
                                 </button>
                             </fieldset>
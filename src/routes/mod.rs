@@ -48,21 +48,19 @@ pub struct HistoryState {
 
 impl AppRoute {
     pub fn requires_auth(&self) -> bool {
-        match &self {
-            AppRoute::Login => false,
-            AppRoute::ForgotPassword => false,
-            AppRoute::ResetPassword => false,
-            AppRoute::NotFound(_) => false,
-            _ => true,
-        }
+        !matches!(
+            &self,
+            AppRoute::Login
+                | AppRoute::ForgotPassword
+                | AppRoute::ResetPassword
+                | AppRoute::NotFound(_)
+        )
     }
     pub fn redirect_if_logged_in(&self) -> bool {
-        match &self {
-            AppRoute::Login => true,
-            AppRoute::ForgotPassword => true,
-            AppRoute::ResetPassword => true,
-            _ => false,
-        }
+        matches!(
+            &self,
+            AppRoute::Login | AppRoute::ForgotPassword | AppRoute::ResetPassword
+        )
     }
 }
 
@@ -72,9 +70,7 @@ pub fn on_route_change(new_route: Route, is_authenticated: bool) {
         Some(a) => {
             let mut dispatch = RouteAgentDispatcher::new();
             if a.requires_auth() && !is_authenticated {
-                let mut redirect = Route::from(AppRoute::Login);
-                redirect.state.redirect_on_login = Some(new_route.route);
-                dispatch.send(RouteRequest::ReplaceRoute(redirect))
+                dispatch.send(RouteRequest::ReplaceRoute(login_redirect(new_route.route)))
             } else if a.redirect_if_logged_in() && is_authenticated {
                 let redirect = Route::from(AppRoute::Dashboard);
                 dispatch.send(RouteRequest::ReplaceRoute(redirect))
@@ -83,6 +79,13 @@ pub fn on_route_change(new_route: Route, is_authenticated: bool) {
     }
 }
 
+// The login page, which will return to the given route after signing in
+pub fn login_redirect(route: String) -> Route {
+    let mut redirect = Route::from(AppRoute::Login);
+    redirect.state.redirect_on_login = Some(route);
+    redirect
+}
+
 pub type State = HistoryState;
 #[allow(dead_code)]
 pub type Route = yew_router::route::Route<State>;
@@ -1,14 +1,16 @@
 use crate::api::error::APIError;
+use crate::api::transport::RequestTask;
 use crate::api::APIClient;
 use crate::components::error::ErrorAlert;
+use crate::components::lockout::LockoutAlert;
 use crate::form_data::GetFormData;
 use crate::loader_task::LoadingFunction;
 use crate::routes::{AppRoute, Route, RouteAgentDispatcher, RouteService};
 use serde::Deserialize;
+use std::time::Duration;
 use thiserror::Error;
 use web_sys::FormData;
 use yew::prelude::*;
-use yew::services::fetch::FetchTask;
 use yew_router::agent::RouteRequest;
 
 const FIELD_PWD: &str = "password";
@@ -54,8 +56,9 @@ pub struct PasswordResetRoute {
     props: Props,
     link: ComponentLink<Self>,
     error: Option<Error>,
-    task: Option<FetchTask>,
+    task: Option<RequestTask>,
     form: Form,
+    lockout: Option<Duration>,
 }
 
 #[derive(Properties, Clone, PartialEq)]
@@ -67,6 +70,7 @@ pub struct Props {
 pub enum Msg {
     Submit(FormData),
     Response(Result<(), APIError>),
+    LockoutExpired,
 }
 
 impl Component for PasswordResetRoute {
@@ -76,13 +80,14 @@ impl Component for PasswordResetRoute {
     fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
         let q = RouteService::new().get_query();
         let q = q.strip_prefix("?").unwrap_or("");
-        let q: Option<Query> = serde_urlencoded::from_str(&q).ok();
+        let q: Option<Query> = serde_urlencoded::from_str(q).ok();
         Self {
             props,
             link,
             error: None,
             task: None,
             form: Form::new(q),
+            lockout: None,
         }
     }
 
@@ -93,7 +98,7 @@ impl Component for PasswordResetRoute {
                 self.form.repeat_password = fd.get(FIELD_PWD_REPEAT).as_string().unwrap();
                 if self.form.password == self.form.repeat_password {
                     self.error = None;
-                    if self.task.is_none() {
+                    if self.task.is_none() && self.lockout.is_none() {
                         self.task = Some(self.props.api_client.password_reset_submit(
                             self.form.email.clone(),
                             self.form.token.clone(),
@@ -114,10 +119,16 @@ impl Component for PasswordResetRoute {
                         agent.send(RouteRequest::ChangeRoute(Route::from(AppRoute::Login)));
                     }
                     Err(e) => {
-                        self.error = Some(Error::APIError(e));
+                        self.lockout = e.retry_after();
+                        if self.lockout.is_none() {
+                            self.error = Some(Error::APIError(e));
+                        }
                     }
                 }
             }
+            Msg::LockoutExpired => {
+                self.lockout = None;
+            }
         }
         true
     }
@@ -132,7 +143,7 @@ impl Component for PasswordResetRoute {
     }
 
     fn view(&self) -> Html {
-        let onsubmit = self.link.on_form_submit(|f| Msg::Submit(f));
+        let onsubmit = self.link.on_form_submit(Msg::Submit);
         html! {
             <div class="container">
                 <div class="row">
@@ -168,10 +179,14 @@ impl Component for PasswordResetRoute {
                                         />
                                 </fieldset>
                                 <ErrorAlert<Error> error=&self.error />
+                                <LockoutAlert
+                                    retry_after=self.lockout
+                                    on_expired=self.link.callback(|_| Msg::LockoutExpired)
+                                />
                                 <button
                                     class="btn btn-lg btn-primary"
                                     type="submit"
-                                    disabled=self.task.is_some()>
+                                    disabled=self.task.is_some() || self.lockout.is_some()>
                                     { "Change Password" }
                                 </button>
                             </fieldset>
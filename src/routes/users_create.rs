@@ -1,13 +1,14 @@
 use crate::api::error::APIError;
+use crate::api::transport::RequestTask;
 use crate::api::users::UserResponseItem;
 use crate::api::APIClient;
 use crate::components::error::ErrorAlert;
+use crate::components::field_error::{input_classes, FieldError, FORM_CONTROL};
 use crate::form_data::GetFormData;
 use crate::loader_task::LoadingFunction;
 use crate::routes::{AppRoute, Route, RouteAgentDispatcher};
 use web_sys::FormData;
 use yew::prelude::*;
-use yew::services::fetch::FetchTask;
 use yew_router::agent::RouteRequest;
 
 const FIELD_NAME: &str = "name";
@@ -23,7 +24,7 @@ pub struct CreateUserRoute {
     props: Props,
     link: ComponentLink<Self>,
     error: Option<APIError>,
-    task: Option<FetchTask>,
+    task: Option<RequestTask>,
     form: Form,
 }
 
@@ -93,7 +94,7 @@ impl Component for CreateUserRoute {
     }
 
     fn view(&self) -> Html {
-        let onsubmit = self.link.on_form_submit(|f| Msg::Submit(f));
+        let onsubmit = self.link.on_form_submit(Msg::Submit);
         html! {
             <div class="container">
                 <div class="row">
@@ -103,20 +104,22 @@ impl Component for CreateUserRoute {
                             <fieldset>
                                 <fieldset class="form-group">
                                     <input
-                                        class="form-control form-control-lg"
+                                        class=input_classes(FORM_CONTROL, &self.error, FIELD_NAME)
                                         placeholder="Name"
                                         value=&self.form.name
                                         name=FIELD_NAME
                                         />
+                                    <FieldError error=&self.error field=FIELD_NAME />
                                 </fieldset>
                                 <fieldset class="form-group">
                                     <input
-                                        class="form-control form-control-lg"
+                                        class=input_classes(FORM_CONTROL, &self.error, FIELD_EMAIL)
                                         type="email"
                                         placeholder="Email"
                                         value=&self.form.email
                                         name=FIELD_EMAIL
                                         />
+                                    <FieldError error=&self.error field=FIELD_EMAIL />
                                 </fieldset>
                                 <ErrorAlert<APIError> error=&self.error />
                                 <p>{"Note: The user will be sent an email allowing them to set their password"}</p>
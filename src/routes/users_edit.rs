@@ -1,13 +1,15 @@
 use crate::api::error::APIError;
+use crate::api::transport::RequestTask;
 use crate::api::users::UserResponseItem;
 use crate::api::APIClient;
+use crate::components::conflict::{ConflictComponent, ConflictField};
 use crate::components::error::ErrorAlert;
+use crate::components::field_error::{input_classes, FieldError, FORM_CONTROL};
 use crate::form_data::GetFormData;
 use crate::loader_task::LoadingFunction;
 use crate::routes::{AppRoute, Route, RouteAgentDispatcher};
 use web_sys::FormData;
 use yew::prelude::*;
-use yew::services::fetch::FetchTask;
 use yew_router::agent::RouteRequest;
 
 const FIELD_NAME: &str = "name";
@@ -22,12 +24,14 @@ struct Form {
 pub struct EditUserRoute {
     props: Props,
     link: ComponentLink<Self>,
-    load_task: Option<FetchTask>,
-    task: Option<FetchTask>,
+    load_task: Option<RequestTask>,
+    task: Option<RequestTask>,
     load_error: Option<APIError>,
     edit_error: Option<APIError>,
     delete_error: Option<APIError>,
     form: Form,
+    version: Option<u32>,
+    conflict: Option<UserResponseItem>, // The current version, if someone else changed it
 }
 
 #[derive(Properties, Clone, PartialEq)]
@@ -41,6 +45,9 @@ pub enum Msg {
     LoadResponse(Result<UserResponseItem, APIError>),
     Submit(FormData),
     EditResponse(Result<UserResponseItem, APIError>),
+    ConflictResponse(Result<UserResponseItem, APIError>),
+    Overwrite,
+    Reload,
     ConfirmDelete,
     DeleteResponse(Result<(), APIError>),
 }
@@ -64,6 +71,8 @@ impl Component for EditUserRoute {
             edit_error: None,
             delete_error: None,
             form: Default::default(),
+            version: None,
+            conflict: None,
         }
     }
 
@@ -72,15 +81,8 @@ impl Component for EditUserRoute {
             Msg::Submit(fd) => {
                 self.form.name = fd.get(FIELD_NAME).as_string().unwrap();
                 self.form.email = fd.get(FIELD_EMAIL).as_string().unwrap();
-                if self.load_task.is_none() && self.task.is_none() {
-                    self.edit_error = None;
-                    self.task = Some(self.props.api_client.users_update(
-                        self.props.user_id,
-                        self.form.name.clone(),
-                        self.form.email.clone(),
-                        self.props.on_loading.clone(),
-                        self.link.callback(Msg::EditResponse),
-                    ));
+                if self.load_task.is_none() && self.task.is_none() && self.conflict.is_none() {
+                    self.send_update();
                 }
             }
             Msg::LoadResponse(r) => {
@@ -89,6 +91,7 @@ impl Component for EditUserRoute {
                     Ok(x) => {
                         self.form.name = x.name;
                         self.form.email = x.email;
+                        self.version = x.version;
                     }
                     Err(e) => {
                         self.load_error = Some(e);
@@ -98,15 +101,43 @@ impl Component for EditUserRoute {
             Msg::EditResponse(r) => {
                 self.task = None;
                 match r {
-                    Ok(_) => {
+                    // A queued change is shown in the header, and will be sent once back online
+                    Ok(_) | Err(APIError::Queued) => {
                         let mut agent = RouteAgentDispatcher::new();
                         agent.send(RouteRequest::ChangeRoute(Route::from(AppRoute::Users)));
                     }
+                    Err(APIError::Conflict(_)) => {
+                        self.task = Some(self.props.api_client.users_get(
+                            self.props.user_id,
+                            Some(self.props.on_loading.clone()),
+                            self.link.callback(Msg::ConflictResponse),
+                        ));
+                    }
                     Err(e) => {
                         self.edit_error = Some(e);
                     }
                 }
             }
+            Msg::ConflictResponse(r) => {
+                self.task = None;
+                match r {
+                    Ok(x) => self.conflict = Some(x),
+                    Err(e) => self.edit_error = Some(e),
+                }
+            }
+            Msg::Overwrite => {
+                if let Some(x) = self.conflict.take() {
+                    self.version = x.version;
+                    self.send_update();
+                }
+            }
+            Msg::Reload => {
+                if let Some(x) = self.conflict.take() {
+                    self.form.name = x.name;
+                    self.form.email = x.email;
+                    self.version = x.version;
+                }
+            }
             Msg::ConfirmDelete => {
                 self.delete_error = None;
                 self.task = Some(self.props.api_client.users_delete(
@@ -165,8 +196,47 @@ impl Component for EditUserRoute {
 }
 
 impl EditUserRoute {
+    fn send_update(&mut self) {
+        self.edit_error = None;
+        self.task = Some(self.props.api_client.users_update(
+            self.props.user_id,
+            self.form.name.clone(),
+            self.form.email.clone(),
+            self.version,
+            self.props.on_loading.clone(),
+            self.link.callback(Msg::EditResponse),
+        ));
+    }
+
+    fn conflict(&self) -> Html {
+        match &self.conflict {
+            None => html! {},
+            Some(x) => {
+                let fields = vec![
+                    ConflictField {
+                        label: "Name",
+                        server: x.name.clone(),
+                        local: self.form.name.clone(),
+                    },
+                    ConflictField {
+                        label: "Email",
+                        server: x.email.clone(),
+                        local: self.form.email.clone(),
+                    },
+                ];
+                html! {
+                    <ConflictComponent
+                        fields=fields
+                        on_overwrite=self.link.callback(|_| Msg::Overwrite)
+                        on_reload=self.link.callback(|_| Msg::Reload)
+                    />
+                }
+            }
+        }
+    }
+
     fn form(&self) -> Html {
-        let onsubmit = self.link.on_form_submit(|f| Msg::Submit(f));
+        let onsubmit = self.link.on_form_submit(Msg::Submit);
         html! {
         <>
             <h1 class="mb-3">{ "Edit user" }</h1>
@@ -174,27 +244,30 @@ impl EditUserRoute {
                 <fieldset>
                     <fieldset class="form-group">
                         <input
-                            class="form-control form-control-lg"
+                            class=input_classes(FORM_CONTROL, &self.edit_error, FIELD_NAME)
                             placeholder="Name"
                             value=&self.form.name
                             name=FIELD_NAME
                             />
+                        <FieldError error=&self.edit_error field=FIELD_NAME />
                     </fieldset>
                     <fieldset class="form-group">
                         <input
-                            class="form-control form-control-lg"
+                            class=input_classes(FORM_CONTROL, &self.edit_error, FIELD_EMAIL)
                             type="email"
                             placeholder="Email"
                             value=&self.form.email
                             name=FIELD_EMAIL
                             />
+                        <FieldError error=&self.edit_error field=FIELD_EMAIL />
                     </fieldset>
                     <p>{"Make sure the email is correct, since it can be used to reset the password!"}</p>
                     <ErrorAlert<APIError> error=&self.edit_error />
+                    { self.conflict() }
                     <button
                         class="btn btn-lg btn-primary"
                         type="submit"
-                        disabled=self.task.is_some()
+                        disabled=self.task.is_some() || self.conflict.is_some()
                         > { "Update" }
                     </button>
                 </fieldset>
@@ -1,4 +1,5 @@
 use crate::api::error::APIError;
+use crate::api::transport::RequestTask;
 use crate::api::users::UserResponseItem;
 use crate::api::{APIClient, Counted};
 use crate::components::error::ErrorAlert;
@@ -7,14 +8,13 @@ use crate::components::search::SearchBarComponent;
 use crate::loader_task::LoadingFunction;
 use crate::routes::{AppRoute, RouterAnchor};
 use yew::prelude::*;
-use yew::services::fetch::FetchTask;
 
 const PAGE_SIZE: u32 = 10;
 
 pub struct ListUsersRoute {
     props: Props,
     link: ComponentLink<Self>,
-    task: Option<FetchTask>,
+    task: Option<RequestTask>,
     error: Option<APIError>,
     search: Option<String>,
     results: Option<Counted<UserResponseItem>>,
@@ -31,6 +31,7 @@ pub enum Msg {
     PageChange(u32),
     SearchChange(Option<String>),
     Response(Result<Counted<UserResponseItem>, APIError>),
+    Refreshed,
 }
 
 impl Component for ListUsersRoute {
@@ -43,6 +44,7 @@ impl Component for ListUsersRoute {
             0,
             None,
             props.on_loading.clone(),
+            link.callback(|_| Msg::Refreshed),
             link.callback(Msg::Response),
         );
         Self {
@@ -59,7 +61,8 @@ impl Component for ListUsersRoute {
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match msg {
             Msg::Response(r) => {
-                self.task = None;
+                // Cached data may still be checked in the background, which shouldn't be cancelled
+                self.task = self.task.take().filter(|t| t.is_active());
                 self.results = None;
                 match r {
                     Ok(x) => {
@@ -78,6 +81,11 @@ impl Component for ListUsersRoute {
                 self.offset = x * PAGE_SIZE;
                 self.reload();
             }
+            // The page shown came from the cache, and has since changed
+            Msg::Refreshed => {
+                self.reload();
+                return false;
+            }
         }
         true
     }
@@ -92,13 +100,13 @@ impl Component for ListUsersRoute {
     }
 
     fn view(&self) -> Html {
-        let page_change = self.link.callback(|x| Msg::PageChange(x));
+        let page_change = self.link.callback(Msg::PageChange);
         let page = self.offset / PAGE_SIZE;
         let total_pages = match &self.results {
             None => 1,
             Some(x) => (x.total as f32 / PAGE_SIZE as f32).ceil() as u32,
         };
-        let search_change = self.link.callback(|s| Msg::SearchChange(s));
+        let search_change = self.link.callback(Msg::SearchChange);
         html! {
         <>
             <h1 class="mb-3">{ "Users" } </h1>
@@ -169,6 +177,7 @@ impl ListUsersRoute {
             self.offset,
             self.search.clone(),
             self.props.on_loading.clone(),
+            self.link.callback(|_| Msg::Refreshed),
             self.link.callback(Msg::Response),
         ));
     }